@@ -0,0 +1,414 @@
+use std::collections::HashSet;
+use rand::{thread_rng, Rng};
+
+pub const MAX_RADIUS: i32 = 32;
+
+/// Fraction of a spray brush painted each tick, to start with
+const SPRAY_DENSITY: f64 = 0.1;
+const DENSITY_STEP: f64 = 0.05;
+
+/// Every cell on the line from (x0, y0) to (x1, y1), both ends included
+pub fn line(x0: i32, y0: i32, x1: i32, y1: i32) -> Vec<(i32, i32)> {
+    let (dx, dy) = ((x1 - x0).abs(), -(y1 - y0).abs());
+    let (sx, sy) = ((x1 - x0).signum(), (y1 - y0).signum());
+    let mut err = dx + dy;
+    let (mut x, mut y) = (x0, y0);
+    let mut points = vec![(x, y)];
+    while (x, y) != (x1, y1) {
+        let e2 = 2 * err;
+        if e2 >= dy {
+            err += dy;
+            x += sx;
+        }
+        if e2 <= dx {
+            err += dx;
+            y += sy;
+        }
+        points.push((x, y));
+    }
+    points
+}
+
+/// Corners at (x0, y0) and (x1, y1), just the border unless `filled`
+pub fn rect(x0: i32, y0: i32, x1: i32, y1: i32, filled: bool) -> Vec<(i32, i32)> {
+    let mut points = Vec::new();
+    for y in y0.min(y1)..=y0.max(y1) {
+        for x in x0.min(x1)..=x0.max(x1) {
+            let border = x == x0 || x == x1 || y == y0 || y == y1;
+            if filled || border {
+                points.push((x, y));
+            }
+        }
+    }
+    points
+}
+
+/// Centred on (x, y), just the rim unless `filled`
+pub fn circle(x: i32, y: i32, r: i32, filled: bool) -> Vec<(i32, i32)> {
+    // the extra half cell rounds off the flat spots at the edges of small circles
+    let inside = |dx: i32, dy: i32| dx * dx + dy * dy <= r * r + r;
+    let mut points = Vec::new();
+    for dy in -r..=r {
+        for dx in -r..=r {
+            if !inside(dx, dy) {
+                continue;
+            }
+            let rim = !inside(dx - 1, dy) || !inside(dx + 1, dy) || !inside(dx, dy - 1) || !inside(dx, dy + 1);
+            if filled || rim {
+                points.push((x + dx, y + dy));
+            }
+        }
+    }
+    points
+}
+
+/// What dragging with the mouse does
+#[derive(Copy, Clone, PartialEq, Debug)]
+pub enum Tool {
+    Freehand,
+    Line,
+    Rect,
+    /// Centred where the drag started
+    Circle,
+    /// Replace the connected area of one material that was clicked
+    Fill,
+}
+
+impl Tool {
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Tool::Freehand => "Brush",
+            Tool::Line => "Line",
+            Tool::Rect => "Rect",
+            Tool::Circle => "Circle",
+            Tool::Fill => "Fill"
+        }
+    }
+}
+
+/// Which cells already in the world can be painted over
+#[derive(Copy, Clone, PartialEq, Debug)]
+pub enum PaintMode {
+    Overwrite,
+    /// Only into `Air`
+    EmptyOnly,
+    /// Only over the spawner's replace target
+    Replace,
+}
+
+impl PaintMode {
+
+    pub fn next(&self) -> Self {
+        match self {
+            PaintMode::Overwrite => PaintMode::EmptyOnly,
+            PaintMode::EmptyOnly => PaintMode::Replace,
+            PaintMode::Replace => PaintMode::Overwrite
+        }
+    }
+}
+
+/// Copies of every stroke reflected or rotated around a centre
+#[derive(Copy, Clone, PartialEq, Debug)]
+pub enum Symmetry {
+    Off,
+    /// Mirrored left to right
+    Horizontal,
+    /// Mirrored top to bottom
+    Vertical,
+    /// N copies spaced evenly around the centre
+    Radial(i32),
+}
+
+pub const MAX_FOLDS: i32 = 8;
+
+impl Symmetry {
+
+    pub fn next(&self) -> Self {
+        match self {
+            Symmetry::Off => Symmetry::Horizontal,
+            Symmetry::Horizontal => Symmetry::Vertical,
+            Symmetry::Vertical => Symmetry::Radial(4),
+            Symmetry::Radial(_) => Symmetry::Off
+        }
+    }
+
+    /// One more radial copy, wrapping back round to two
+    pub fn more_folds(&self) -> Self {
+        match self {
+            Symmetry::Radial(n) if *n < MAX_FOLDS => Symmetry::Radial(n + 1),
+            _ => Symmetry::Radial(2)
+        }
+    }
+
+    pub fn name(&self) -> String {
+        match self {
+            Symmetry::Off => "Off".to_owned(),
+            Symmetry::Horizontal => "Mirror X".to_owned(),
+            Symmetry::Vertical => "Mirror Y".to_owned(),
+            Symmetry::Radial(n) => format!("Radial {}", n)
+        }
+    }
+
+    /// Every cell of an area and of all its copies around (cx, cy), which is in half cells so the
+    /// centre can sit on a cell or on the edge between two
+    pub fn apply(&self, points: &[(i32, i32)], cx: i32, cy: i32) -> Vec<(i32, i32)> {
+        let n = match *self {
+            Symmetry::Radial(n) if n != 2 && n != 4 => n,
+            // flips and quarter turns map cells one to one
+            _ => return points.iter().flat_map(|(x, y)| self.images(*x, *y, cx, cy)).collect()
+        };
+        if points.is_empty() {
+            return Vec::new();
+        }
+
+        // rotating cells forward by other angles leaves pinholes, so rotate each cell of a copy back
+        // and check whether it lands in the original instead
+        let source: HashSet<(i32, i32)> = points.iter().cloned().collect();
+        let (ox, oy) = (cx as f32 / 2.0, cy as f32 / 2.0);
+        let x0 = points.iter().map(|p| p.0).min().unwrap() as f32 - ox;
+        let x1 = points.iter().map(|p| p.0).max().unwrap() as f32 - ox;
+        let y0 = points.iter().map(|p| p.1).min().unwrap() as f32 - oy;
+        let y1 = points.iter().map(|p| p.1).max().unwrap() as f32 - oy;
+        let mut copies = points.to_vec();
+        for i in 1..n {
+            let angle = std::f32::consts::PI * 2.0 * i as f32 / n as f32;
+            let (sin, cos) = angle.sin_cos();
+            let corners: Vec<(f32, f32)> = [(x0, y0), (x1, y0), (x0, y1), (x1, y1)].iter()
+                .map(|(x, y)| (ox + x * cos - y * sin, oy + x * sin + y * cos))
+                .collect();
+            let min_x = corners.iter().map(|c| c.0).fold(f32::MAX, f32::min).floor() as i32 - 1;
+            let max_x = corners.iter().map(|c| c.0).fold(f32::MIN, f32::max).ceil() as i32 + 1;
+            let min_y = corners.iter().map(|c| c.1).fold(f32::MAX, f32::min).floor() as i32 - 1;
+            let max_y = corners.iter().map(|c| c.1).fold(f32::MIN, f32::max).ceil() as i32 + 1;
+            for y in min_y..=max_y {
+                for x in min_x..=max_x {
+                    let (dx, dy) = (x as f32 - ox, y as f32 - oy);
+                    let (sx, sy) = (ox + dx * cos + dy * sin, oy - dx * sin + dy * cos);
+                    if source.contains(&(sx.round() as i32, sy.round() as i32)) {
+                        copies.push((x, y));
+                    }
+                }
+            }
+        }
+        copies
+    }
+
+    /// (x, y) and all of its copies around (cx, cy), in half cells like `apply`
+    pub fn images(&self, x: i32, y: i32, cx: i32, cy: i32) -> Vec<(i32, i32)> {
+        match *self {
+            Symmetry::Off => vec![(x, y)],
+            Symmetry::Horizontal => vec![(x, y), (cx - x, y)],
+            Symmetry::Vertical => vec![(x, y), (x, cy - y)],
+            Symmetry::Radial(n) => {
+                let (ox, oy) = (cx as f32 / 2.0, cy as f32 / 2.0);
+                let (dx, dy) = (x as f32 - ox, y as f32 - oy);
+                (0..n).map(|i| {
+                    let angle = std::f32::consts::PI * 2.0 * i as f32 / n as f32;
+                    let (sin, cos) = angle.sin_cos();
+                    ((ox + dx * cos - dy * sin).round() as i32, (oy + dx * sin + dy * cos).round() as i32)
+                }).collect()
+            }
+        }
+    }
+}
+
+#[derive(Copy, Clone, PartialEq, Debug)]
+pub enum BrushShape {
+    Circle,
+    Square,
+    /// Horizontal, for laying down floors and ledges
+    Line,
+    /// Random scattering over a circle
+    Spray,
+}
+
+impl BrushShape {
+
+    pub fn next(&self) -> Self {
+        match self {
+            BrushShape::Circle => BrushShape::Square,
+            BrushShape::Square => BrushShape::Line,
+            BrushShape::Line => BrushShape::Spray,
+            BrushShape::Spray => BrushShape::Circle
+        }
+    }
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            BrushShape::Circle => "Circle",
+            BrushShape::Square => "Square",
+            BrushShape::Line => "Line",
+            BrushShape::Spray => "Spray"
+        }
+    }
+}
+
+/// Cells painted around the cursor, centred on it
+pub struct Brush {
+    shape: BrushShape,
+    radius: i32,
+    /// Fraction of the area a spray paints each tick
+    density: f64,
+    deltas: Vec<(i32, i32)>,
+    /// Edge of `deltas`, for showing where the brush will paint
+    outline: Vec<(i32, i32)>,
+}
+
+impl Brush {
+
+    pub fn new(shape: BrushShape, radius: i32) -> Self {
+        let radius = radius.clamp(0, MAX_RADIUS);
+        let mut brush = Brush {
+            shape,
+            radius,
+            density: SPRAY_DENSITY,
+            deltas: Vec::new(),
+            outline: Vec::new(),
+        };
+        brush.rebuild();
+        brush
+    }
+
+    fn rebuild(&mut self) {
+        self.deltas = Brush::build(self.shape, self.radius);
+        let inside: HashSet<(i32, i32)> = self.deltas.iter().cloned().collect();
+        self.outline = self.deltas.iter()
+            .filter(|(x, y)| [(x - 1, *y), (x + 1, *y), (*x, y - 1), (*x, y + 1)].iter().any(|p| !inside.contains(p)))
+            .cloned()
+            .collect();
+    }
+
+    fn build(shape: BrushShape, r: i32) -> Vec<(i32, i32)> {
+        match shape {
+            BrushShape::Line => line(-r, 0, r, 0),
+            BrushShape::Square => rect(-r, -r, r, r, true),
+            BrushShape::Circle | BrushShape::Spray => circle(0, 0, r, true)
+        }
+    }
+
+    pub fn shape(&self) -> BrushShape {
+        self.shape
+    }
+
+    pub fn radius(&self) -> i32 {
+        self.radius
+    }
+
+    pub fn density(&self) -> f64 {
+        self.density
+    }
+
+    pub fn set_shape(&mut self, shape: BrushShape) {
+        self.shape = shape;
+        self.rebuild();
+    }
+
+    pub fn set_radius(&mut self, radius: i32) {
+        self.radius = radius.clamp(0, MAX_RADIUS);
+        self.rebuild();
+    }
+
+    /// Spray more or less of the brush each tick
+    pub fn change_density(&mut self, steps: i32) {
+        self.density = (self.density + DENSITY_STEP * steps as f64).clamp(DENSITY_STEP, 1.0);
+    }
+
+    /// Every offset the brush can paint, ignoring spray
+    pub fn footprint(&self) -> &[(i32, i32)] {
+        &self.deltas
+    }
+
+    pub fn outline(&self) -> &[(i32, i32)] {
+        &self.outline
+    }
+
+    /// Which of the cells the brush swept over this tick get painted, a spray keeps a different
+    /// `density` of them every call
+    pub fn spray(&self, swept: impl IntoIterator<Item = (i32, i32)>) -> Vec<(i32, i32)> {
+        match self.shape {
+            BrushShape::Spray => {
+                let mut rng = thread_rng();
+                swept.into_iter().filter(|_| rng.gen_bool(self.density)).collect()
+            },
+            _ => swept.into_iter().collect()
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Cells left out of `points` with all four sides painted
+    fn pinholes(points: &[(i32, i32)]) -> Vec<(i32, i32)> {
+        let painted: HashSet<(i32, i32)> = points.iter().cloned().collect();
+        let (x0, x1) = (points.iter().map(|p| p.0).min().unwrap(), points.iter().map(|p| p.0).max().unwrap());
+        let (y0, y1) = (points.iter().map(|p| p.1).min().unwrap(), points.iter().map(|p| p.1).max().unwrap());
+        rect(x0, y0, x1, y1, true).into_iter()
+            .filter(|p| !painted.contains(p))
+            .filter(|(x, y)| [(x - 1, *y), (x + 1, *y), (*x, y - 1), (*x, y + 1)].iter().all(|p| painted.contains(p)))
+            .collect()
+    }
+
+    #[test]
+    fn radial_copies_have_no_pinholes() {
+        // far enough from the centre that no two copies touch
+        let filled = circle(168, 128, 10, true);
+        for n in [3, 5, 6, 8].iter() {
+            let symmetry = Symmetry::Radial(*n);
+            let copies = symmetry.apply(&filled, 255, 255);
+            assert_eq!(pinholes(&copies), vec![], "Radial {}", n);
+            for (x, y) in symmetry.images(168, 128, 255, 255) {
+                let inner = circle(x, y, 8, true);
+                assert!(inner.iter().all(|p| copies.contains(p)), "Radial {} copy at ({}, {})", n, x, y);
+            }
+        }
+    }
+
+    #[test]
+    fn mirror_default_centre_is_the_middle() {
+        assert_eq!(Symmetry::Horizontal.images(0, 7, 255, 255), vec![(0, 7), (255, 7)]);
+        assert_eq!(Symmetry::Vertical.images(7, 0, 255, 255), vec![(7, 0), (7, 255)]);
+        assert_eq!(Symmetry::Horizontal.images(3, 0, 20, 20), vec![(3, 0), (17, 0)]);
+    }
+
+    #[test]
+    fn line_includes_both_ends() {
+        for (x1, y1) in [(5, 0), (-5, 0), (0, 5), (0, -5), (7, 3), (-3, -7), (4, -4)].iter() {
+            let points = line(0, 0, *x1, *y1);
+            assert_eq!(points.first(), Some(&(0, 0)));
+            assert_eq!(points.last(), Some(&(*x1, *y1)));
+            assert_eq!(points.len() as i32, x1.abs().max(y1.abs()) + 1);
+        }
+        assert_eq!(line(2, 3, 2, 3), vec![(2, 3)]);
+    }
+
+    #[test]
+    fn rect_includes_corners() {
+        let outline = rect(4, 6, 0, 0, false);
+        let filled = rect(4, 6, 0, 0, true);
+        for corner in [(0, 0), (4, 0), (0, 6), (4, 6)].iter() {
+            assert!(outline.contains(corner));
+            assert!(filled.contains(corner));
+        }
+        assert_eq!(filled.len(), 5 * 7);
+        assert_eq!(outline.len(), 5 * 7 - 3 * 5);
+        assert!(!outline.contains(&(2, 3)));
+    }
+
+    #[test]
+    fn circle_reaches_its_radius() {
+        for r in 0..=MAX_RADIUS {
+            let rim = circle(10, 20, r, false);
+            let filled = circle(10, 20, r, true);
+            for end in [(10 + r, 20), (10 - r, 20), (10, 20 + r), (10, 20 - r)].iter() {
+                assert!(rim.contains(end), "radius {}", r);
+                assert!(filled.contains(end), "radius {}", r);
+            }
+            assert!(!filled.contains(&(10 + r + 1, 20)));
+            assert!(filled.contains(&(10, 20)));
+            assert_eq!(rim.contains(&(10, 20)), r == 0);
+        }
+    }
+}
@@ -0,0 +1,103 @@
+use std::fs::File;
+use std::io::BufWriter;
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+
+use crate::game::GameState;
+
+/// Picture of the world with every cell blown up to `scale` x `scale` pixels
+pub fn scaled_pixels(state: &GameState, scale: u32) -> Vec<u8> {
+    let size = state.size as usize;
+    let scale = scale.max(1) as usize;
+    if scale == 1 {
+        return state.pixels().to_vec();
+    }
+    let mut pixels = Vec::with_capacity(size * size * scale * scale * 3);
+    for row in state.pixels().chunks(size * 3) {
+        let mut scaled_row = Vec::with_capacity(size * scale * 3);
+        for p in row.chunks(3) {
+            for _ in 0..scale {
+                scaled_row.extend_from_slice(p);
+            }
+        }
+        for _ in 0..scale {
+            pixels.extend_from_slice(&scaled_row);
+        }
+    }
+    pixels
+}
+
+/// `<prefix>-<unix time>.<extension>` in the working directory
+fn timestamped(prefix: &str, extension: &str) -> String {
+    let now = SystemTime::now().duration_since(UNIX_EPOCH).map(|d| d.as_secs()).unwrap_or(0);
+    format!("{}-{}.{}", prefix, now, extension)
+}
+
+pub fn write_png(path: &str, pixels: &[u8], width: u32, height: u32) -> Result<(), String> {
+    let file = File::create(path).map_err(|e| e.to_string())?;
+    let mut encoder = png::Encoder::new(BufWriter::new(file), width, height);
+    encoder.set_color(png::ColorType::Rgb);
+    encoder.set_depth(png::BitDepth::Eight);
+    let mut writer = encoder.write_header().map_err(|e| e.to_string())?;
+    writer.write_image_data(pixels).map_err(|e| e.to_string())
+}
+
+/// Save the world to `screenshot-<unix time>.png`, returning the file name
+pub fn screenshot(state: &GameState, scale: u32) -> Result<String, String> {
+    let path = timestamped("screenshot", "png");
+    let side = state.size as u32 * scale.max(1);
+    write_png(&path, &scaled_pixels(state, scale), side, side)?;
+    Ok(path)
+}
+
+/// Shortest time between recorded frames, 25 fps
+const FRAME_INTERVAL: Duration = Duration::from_millis(40);
+
+/// Animated GIF of the world, finished when dropped
+pub struct Recorder {
+    path: String,
+    encoder: gif::Encoder<BufWriter<File>>,
+    scale: u32,
+    last_frame: Instant,
+}
+
+impl Recorder {
+
+    /// Start recording to `recording-<unix time>.gif`
+    pub fn start(state: &GameState, scale: u32) -> Result<Recorder, String> {
+        let path = timestamped("recording", "gif");
+        let side = (state.size as u32 * scale.max(1)) as u16;
+        let file = File::create(&path).map_err(|e| e.to_string())?;
+        let mut encoder = gif::Encoder::new(BufWriter::new(file), side, side, &[]).map_err(|e| e.to_string())?;
+        encoder.set_repeat(gif::Repeat::Infinite).map_err(|e| e.to_string())?;
+
+        let mut recorder = Recorder {
+            path,
+            encoder,
+            scale,
+            last_frame: Instant::now(),
+        };
+        recorder.add_frame(state)?;
+        Ok(recorder)
+    }
+
+    /// Call every frame, records the world at most every `FRAME_INTERVAL`
+    pub fn capture(&mut self, state: &GameState) -> Result<(), String> {
+        if self.last_frame.elapsed() < FRAME_INTERVAL {
+            return Ok(());
+        }
+        self.add_frame(state)
+    }
+
+    fn add_frame(&mut self, state: &GameState) -> Result<(), String> {
+        let side = (state.size as u32 * self.scale.max(1)) as u16;
+        let mut frame = gif::Frame::from_rgb_speed(side, side, &scaled_pixels(state, self.scale), 10);
+        // gif delays are in hundredths of a second, close enough to use the time since the last frame
+        frame.delay = ((self.last_frame.elapsed().as_millis() + 5) / 10).max(2) as u16;
+        self.last_frame = Instant::now();
+        self.encoder.write_frame(&frame).map_err(|e| e.to_string())
+    }
+
+    pub fn path(&self) -> &str {
+        &self.path
+    }
+}
@@ -1,52 +1,180 @@
 use rand::prelude::*;
 use rand::seq::SliceRandom;
 
-use crate::game::{GameState, REGION_SIZE};
+use std::collections::HashSet;
+use crate::brush::{Brush, BrushShape, PaintMode, Symmetry, Tool, circle, line, rect};
+use crate::game::{GameState, REGION_SIZE, DIRECTIONS};
+use crate::material::{with_material, find_material};
 
-#[derive(Copy, Clone, Eq, PartialEq, Hash)]
+#[derive(Copy, Clone, Eq, PartialEq, Hash, Debug)]
 pub enum Cell {
     Air,
-    Sand,
+    Sand{shade: u8},
     Wood,
-    Fire{heat: i32},
+    Fire{heat: i32, shade: u8},
     Seed,
     Vine{growth: i32, grown: bool},
-    Water{dx: i32},
+    Water{dx: i32, shade: u8},
     Acid{t: i32},
     Rocket{last_pos: (i32, i32), i: i32},
     Stone,
     Bomb,
     Destroyed,
-    Ice
+    Ice,
+    Tap{material: usize, t: i32},
+    Void,
+    Cloner{material: Option<usize>},
+    /// A material from `material::register_material`
+    Custom{id: usize, state: i32}
+}
+
+/// Ticks between each cell emitted by a `Tap`
+pub const TAP_INTERVAL: i32 = 4;
+
+/// Materials that a `Tap` or `Cloner` can emit, in their freshly spawned state
+pub const MATERIALS: [Cell; 10] = [
+    Cell::Wood,
+    Cell::Fire{heat: 30, shade: 0},
+    Cell::Seed,
+    Cell::Water{dx: 0, shade: 0},
+    Cell::Acid{t: 0},
+    Cell::Sand{shade: 0},
+    Cell::Rocket{last_pos: (-1, -1), i: -1},
+    Cell::Stone,
+    Cell::Bomb,
+    Cell::Ice
+];
+
+pub fn material_index(cell: Cell) -> Option<usize> {
+    MATERIALS.iter().position(|m| std::mem::discriminant(m) == std::mem::discriminant(&cell))
+}
+
+/// Name of the material, without any of its state
+pub fn cell_name(cell: Cell) -> String {
+    let name = match cell {
+        Cell::Air => "Air",
+        Cell::Sand{..} => "Sand",
+        Cell::Wood => "Wood",
+        Cell::Fire{..} => "Fire",
+        Cell::Seed => "Seed",
+        Cell::Vine{..} => "Vine",
+        Cell::Water{..} => "Water",
+        Cell::Acid{..} => "Acid",
+        Cell::Rocket{..} => "Rocket",
+        Cell::Stone => "Stone",
+        Cell::Bomb => "Bomb",
+        Cell::Destroyed => "Destroyed",
+        Cell::Ice => "Ice",
+        Cell::Tap{..} => "Tap",
+        Cell::Void => "Void",
+        Cell::Cloner{..} => "Cloner",
+        Cell::Custom{id, ..} => {
+            return with_material(id, |m| m.name().to_owned()).unwrap_or_default();
+        }
+    };
+    name.to_owned()
+}
+
+/// Freshly spawned cell of the named material, `state` is only kept by custom materials
+pub fn cell_from_name(name: &str, state: i32) -> Option<Cell> {
+    let others = [Cell::Air, Cell::Vine{growth: 50, grown: false}, Cell::Destroyed, Cell::Void, Cell::Cloner{material: None}];
+    match MATERIALS.iter().chain(others.iter()).find(|c| cell_name(**c) == name) {
+        Some(c) => Some(c.fresh()),
+        None => find_material(name).map(|id| Cell::Custom{id, state})
+    }
+}
+
+impl Cell {
+
+    /// The same cell with a new random shade, for cells being created rather than moved
+    pub fn fresh(self) -> Cell {
+        let shade = thread_rng().gen();
+        match self {
+            Cell::Sand{..} => Cell::Sand{shade},
+            Cell::Fire{heat, ..} => Cell::Fire{heat, shade},
+            Cell::Water{dx, ..} => Cell::Water{dx, shade},
+            c => c
+        }
+    }
+
+    /// The same material as it's first placed, keeping only what says which material it is
+    pub fn spawned(self) -> Cell {
+        if let Some(i) = material_index(self) {
+            return MATERIALS[i];
+        }
+        match self {
+            Cell::Vine{..} => Cell::Vine{growth: 50, grown: false},
+            Cell::Tap{material, ..} => Cell::Tap{material, t: 0},
+            Cell::Custom{id, ..} => Cell::Custom{id, state: 0},
+            c => c
+        }
+    }
+}
+
+/// Which mouse button is painting
+#[derive(Copy, Clone, PartialEq, Debug)]
+pub enum Stroke {
+    Primary,
+    Secondary,
+}
+
+/// A finished drag with one of the shape tools
+struct PendingShape {
+    tool: Tool,
+    from: (i32, i32),
+    to: (i32, i32),
+    stroke: Stroke,
 }
 
 pub struct RadialSpawner{
-    enabled: bool,
+    stroke: Option<Stroke>,
     x: i32,
     y: i32,
-    deltas: Vec<(i32, i32)>,
-    cell: Cell
+    /// Where the brush was last painted in this stroke, to join up with the next position
+    last: Option<(i32, i32)>,
+    /// Where the current drag started, for the shape tools
+    anchor: (i32, i32),
+    /// Shape waiting to be painted on the next spawn
+    pending: Option<PendingShape>,
+    pub brush: Brush,
+    pub tool: Tool,
+    /// Rectangles and circles are filled in rather than outlined
+    pub filled: bool,
+    cell: Cell,
+    /// Weighted materials the left button paints instead of `cell`, when there are any
+    mix: Vec<(Cell, u32)>,
+    /// Painted with the right mouse button, `Air` to erase
+    secondary: Cell,
+    pub mode: PaintMode,
+    pub symmetry: Symmetry,
+    /// What strokes are mirrored around, the middle of the world if not set
+    pub centre: Option<(i32, i32)>,
+    /// The only material painted over in `PaintMode::Replace`
+    target: Cell,
+    /// When erasing with the right button, only remove this material, whatever the paint mode
+    filter: Option<Cell>,
 }
 
 impl RadialSpawner {
     pub fn new(x : i32, y: i32) -> RadialSpawner {
-        let deltas = vec! [
-            (0,0), (1,0), (2,0), (3,0), (4,0), (5,0), (6,0),
-            (0,1), (1,1), (2,1), (3,1), (4,1), (5,1), (6,1),
-        (0,2), (1,2), (2,2), (3,2), (4,2), (5,2), (6,2), (7,2), (8,2),
-        (0,3), (1,3), (2,3), (3,3), (4,3), (5,3), (6,3), (7,3), (8,3),
-        (0,4), (1,4), (2,4), (3,4), (4,4), (5,4), (6,4), (7,4), (8,4),
-        (0,5), (1,5), (2,5), (3,5), (4,5), (5,5), (6,5), (7,5), (8,5),
-        (0,6), (1,6), (2,6), (3,6), (4,6), (5,6), (6,6), (7,6), (8,6),
-            (0,7), (1,7), (2,7), (3,7), (4,7), (5,7), (6,7),
-            (0,8), (1,8), (2,8), (3,8), (4,8), (5,8), (6,8),
-        ];
         RadialSpawner {
             x,
             y,
-            deltas: deltas,
-            enabled: false,
-            cell: Cell::Sand
+            last: None,
+            anchor: (x, y),
+            pending: None,
+            brush: Brush::new(BrushShape::Circle, 4),
+            tool: Tool::Freehand,
+            filled: true,
+            stroke: None,
+            cell: Cell::Sand{shade: 0},
+            mix: Vec::new(),
+            secondary: Cell::Air,
+            mode: PaintMode::Overwrite,
+            symmetry: Symmetry::Off,
+            centre: None,
+            target: Cell::Water{dx: 0, shade: 0},
+            filter: None,
         }
     }
 
@@ -55,19 +183,222 @@ impl RadialSpawner {
         self.y = y;
     }
 
-    pub fn enable(&mut self) {
-        self.enabled = true;
+    pub fn enable(&mut self, stroke: Stroke) {
+        self.stroke = Some(stroke);
+        self.last = None;
+        self.anchor = (self.x, self.y);
     }
 
-    pub fn disable(&mut self) {
-        self.enabled = false;
+    pub fn disable(&mut self, stroke: Stroke) {
+        if self.stroke != Some(stroke) {
+            return;
+        }
+        self.stroke = None;
+        if self.tool != Tool::Freehand {
+            self.pending = Some(PendingShape {
+                tool: self.tool,
+                from: self.anchor,
+                to: (self.x, self.y),
+                stroke,
+            });
+        }
     }
 
     pub fn set_cell(&mut self, cell: Cell) {
-        self.cell = cell
+        self.cell = cell;
+        self.mix.clear();
+    }
+
+    /// Add another part of `cell` to what the left button paints
+    pub fn add_to_mix(&mut self, cell: Cell) {
+        match self.mix.iter_mut().find(|(c, _)| same_material(*c, cell)) {
+            Some((_, weight)) => *weight += 1,
+            None => self.mix.push((cell, 1))
+        }
+        self.cell = self.mix[0].0;
+    }
+
+    pub fn clear_mix(&mut self) {
+        self.mix.clear();
+    }
+
+    pub fn get_mix(&self) -> &[(Cell, u32)] {
+        &self.mix
+    }
+
+    pub fn get_cell(&self) -> Cell {
+        self.cell
+    }
+
+    pub fn set_secondary(&mut self, cell: Cell) {
+        self.secondary = cell
+    }
+
+    pub fn get_secondary(&self) -> Cell {
+        self.secondary
+    }
+
+    /// Only paint over `target` from now on
+    pub fn replace(&mut self, target: Cell) {
+        self.mode = PaintMode::Replace;
+        self.target = target;
+    }
+
+    pub fn get_target(&self) -> Cell {
+        self.target
+    }
+
+    pub fn set_filter(&mut self, filter: Option<Cell>) {
+        self.filter = filter
+    }
+
+    pub fn get_filter(&self) -> Option<Cell> {
+        self.filter
+    }
+
+    pub fn get_pos(&self) -> (i32, i32) {
+        (self.x, self.y)
+    }
+
+    fn stroke_cell(&self, stroke: Stroke) -> Cell {
+        match stroke {
+            Stroke::Primary => self.cell,
+            Stroke::Secondary => self.secondary
+        }
+    }
+
+    /// Cell to put down at one point of a stroke, rolling the mix if there is one
+    fn pick(&self, stroke: Stroke) -> Cell {
+        if stroke == Stroke::Secondary || self.mix.is_empty() {
+            return self.stroke_cell(stroke).fresh();
+        }
+        let total: u32 = self.mix.iter().map(|(_, weight)| weight).sum();
+        let mut roll = thread_rng().gen_range(0, total);
+        for (cell, weight) in self.mix.iter() {
+            if roll < *weight {
+                return cell.fresh();
+            }
+            roll -= weight;
+        }
+        self.cell.fresh()
+    }
+
+    /// Cells covered by dragging a shape tool from `from` to `to`
+    fn shape(&self, tool: Tool, from: (i32, i32), to: (i32, i32)) -> Vec<(i32, i32)> {
+        let ((x0, y0), (x1, y1)) = (from, to);
+        let points = match tool {
+            Tool::Line => line(x0, y0, x1, y1),
+            Tool::Rect => rect(x0, y0, x1, y1, self.filled),
+            Tool::Circle => {
+                let r = (((x1 - x0).pow(2) + (y1 - y0).pow(2)) as f32).sqrt().round() as i32;
+                circle(x0, y0, r, self.filled)
+            },
+            Tool::Freehand | Tool::Fill => vec![to]
+        };
+        if self.filled && tool != Tool::Line {
+            return points;
+        }
+        // lines and outlines are as thick as the brush
+        let mut stamped: Vec<(i32, i32)> = points.iter()
+            .flat_map(|(x, y)| self.brush.footprint().iter().map(move |(dx, dy)| (x + dx, y + dy)))
+            .collect::<HashSet<_>>()
+            .into_iter()
+            .collect();
+        stamped.sort();
+        stamped
+    }
+
+    /// In half cells, see `Symmetry::apply`, by default between the two middle cells of the world
+    fn symmetry_centre(&self, size: i32) -> (i32, i32) {
+        self.centre.map_or((size - 1, size - 1), |(x, y)| (2 * x, 2 * y))
+    }
+
+    /// Every point along with its copies from the symmetry, in a world `size` cells across
+    fn mirror(&self, points: Vec<(i32, i32)>, size: i32) -> Vec<(i32, i32)> {
+        if self.symmetry == Symmetry::Off {
+            return points;
+        }
+        let (cx, cy) = self.symmetry_centre(size);
+        let mut mirrored = self.symmetry.apply(&points, cx, cy);
+        mirrored.sort();
+        mirrored.dedup();
+        mirrored
+    }
+
+    /// The shape being dragged out and what it'll be painted with, for drawing before it's placed
+    pub fn preview(&self, size: i32) -> Option<(Vec<(i32, i32)>, Cell)> {
+        let stroke = self.stroke?;
+        match self.tool {
+            Tool::Freehand | Tool::Fill => None,
+            tool => Some((self.mirror(self.shape(tool, self.anchor, (self.x, self.y)), size), self.stroke_cell(stroke)))
+        }
+    }
+
+    /// Edge of the brush around the cursor and the colour it'll paint, when not dragging out a shape
+    pub fn cursor(&self, size: i32) -> (Vec<(i32, i32)>, Cell) {
+        let mut points = match self.tool {
+            Tool::Fill => vec![(self.x, self.y)],
+            _ => self.brush.outline().iter().map(|(dx, dy)| (self.x + dx, self.y + dy)).collect()
+        };
+        if self.symmetry != Symmetry::Off {
+            // small cross on the centre of symmetry
+            let (cx, cy) = self.symmetry_centre(size);
+            let (cx, cy) = (cx / 2, cy / 2);
+            points.extend((-2..=2).flat_map(|d| vec![(cx + d, cy), (cx, cy + d)]));
+        }
+        (self.mirror(points, size), self.cell)
+    }
+
+    fn paint(&self, write_state: &mut GameState, points: impl IntoIterator<Item = (i32, i32)>, stroke: Stroke) {
+        // the eraser ignores the paint mode, only the filter limits what it removes
+        let erasing = stroke == Stroke::Secondary && self.secondary == Cell::Air;
+        for (x, y) in points {
+            let current = *write_state.read_cell(x, y);
+            let allowed = match (erasing, self.mode) {
+                (true, _) => self.filter.is_none_or(|f| same_material(current, f)),
+                (false, PaintMode::Overwrite) => true,
+                (false, PaintMode::EmptyOnly) => current == Cell::Air,
+                (false, PaintMode::Replace) => same_material(current, self.target)
+            };
+            if allowed {
+                write_state.write_cell(self.pick(stroke), x, y, true);
+            }
+        }
+    }
+}
+
+/// Same material, ignoring any state it carries
+pub fn same_material(a: Cell, b: Cell) -> bool {
+    match (a, b) {
+        (Cell::Custom{id: a, ..}, Cell::Custom{id: b, ..}) => a == b,
+        _ => std::mem::discriminant(&a) == std::mem::discriminant(&b)
     }
 }
 
+/// Every cell connected to (x, y) through sides that's the same material as it
+pub fn flood(state: &GameState, x: i32, y: i32) -> Vec<(i32, i32)> {
+    let size = state.size;
+    let inside = |x: i32, y: i32| x >= 0 && y >= 0 && x < size && y < size;
+    if !inside(x, y) {
+        return Vec::new();
+    }
+    let target = *state.read_cell(x, y);
+    let mut seen = vec![false; (size * size) as usize];
+    let mut stack = vec![(x, y)];
+    let mut points = Vec::new();
+    seen[(y * size + x) as usize] = true;
+    while let Some((x, y)) = stack.pop() {
+        points.push((x, y));
+        for (nx, ny) in [(x - 1, y), (x + 1, y), (x, y - 1), (x, y + 1)] {
+            if inside(nx, ny) && !seen[(ny * size + nx) as usize] && same_material(*state.read_cell(nx, ny), target) {
+                seen[(ny * size + nx) as usize] = true;
+                stack.push((nx, ny));
+            }
+        }
+    }
+    points
+}
+
 pub trait Spawner {
 
     fn spawn(&mut self, write_state: &mut GameState);
@@ -78,13 +409,38 @@ impl Spawner for RadialSpawner {
 
     fn spawn(&mut self, write_state: &mut GameState) {
 
-        if !self.enabled {
-            return;
+        let size = write_state.size;
+        if let Some(shape) = self.pending.take() {
+            let points = match shape.tool {
+                // fill from each copy of the click, rather than copying one filled area over whatever's elsewhere
+                Tool::Fill => {
+                    let (cx, cy) = self.symmetry_centre(size);
+                    self.symmetry.images(shape.from.0, shape.from.1, cx, cy).into_iter()
+                        .flat_map(|(x, y)| flood(write_state, x, y))
+                        .collect()
+                },
+                tool => self.mirror(self.shape(tool, shape.from, shape.to), size)
+            };
+            self.paint(write_state, points, shape.stroke);
         }
 
-        for (dx, dy) in self.deltas.iter() {
-            write_state.write_cell(self.cell, self.x + dx, self.y + dy, true);
-        }
+        let stroke = match (self.stroke, self.tool) {
+            (Some(stroke), Tool::Freehand) => stroke,
+            _ => return
+        };
+
+        // the mouse can move many cells between ticks, paint everywhere it went
+        let path = match self.last {
+            Some((x, y)) => line(x, y, self.x, self.y),
+            None => vec![(self.x, self.y)]
+        };
+        // overlapping stamps along the path only get painted once, and sprayed once so a moving
+        // spray is no denser than a still one
+        let swept: HashSet<(i32, i32)> = path.iter()
+            .flat_map(|(x, y)| self.brush.footprint().iter().map(move |(dx, dy)| (x + dx, y + dy)))
+            .collect();
+        self.paint(write_state, self.mirror(self.brush.spray(swept), size), stroke);
+        self.last = Some((self.x, self.y));
     }
 
 }
@@ -98,25 +454,46 @@ pub fn random_dir(x: i32, y: i32) -> (i32, i32) {
     (random_axis(x), random_axis(y))
 }
 
+/// Neighbour of (x, y) in one of the `DIRECTIONS`
+pub fn step(x: i32, y: i32, dir: usize) -> (i32, i32) {
+    let (dx, dy) = DIRECTIONS[dir % 8];
+    (x + dx, y + dy)
+}
+
+/// Random direction within an eighth of a turn of `dir`
+fn spread(dir: usize) -> usize {
+    (dir + *[7, 8, 9].choose(&mut thread_rng()).unwrap()) % 8
+}
+
 pub fn update_cell(cell: Cell, x: i32, y: i32, read_state: &GameState, write_state: &mut GameState) {
     
     if !write_state.is_empty(x, y) {
         return;
     }
 
+    match cell {
+        Cell::Air | Cell::Void | Cell::Tap{..} | Cell::Cloner{..} => {},
+        _ => {
+            if touching_void(x, y, read_state) {
+                return;
+            }
+        }
+    }
 
     let mut rng = rand::thread_rng();
+    let down = read_state.gravity.down();
+    let up = read_state.gravity.up();
 
     match cell {
         Cell::Air => {},
         Cell::Stone => {
             write_state.write_cell(Cell::Stone, x, y, false);
         }
-        Cell::Sand => {
+        Cell::Sand{..} => {
             if dissolve_in_acid(x, y, read_state, write_state) == AcidResult::Dissolved {
                 return;
             }
-            let _ = gravity(Cell::Sand, x, y, read_state, write_state);
+            let _ = gravity(cell, x, y, read_state, write_state);
         },
         Cell::Wood => {
             if burn_near_fire(x, y, read_state, write_state) == FireResult::Burnt {
@@ -129,7 +506,7 @@ pub fn update_cell(cell: Cell, x: i32, y: i32, read_state: &GameState, write_sta
 
             write_state.write_cell(Cell::Wood, x, y, false);
         },
-        Cell::Fire{heat} => {
+        Cell::Fire{heat, shade} => {
             if heat <= 0 {
                 return;
             }
@@ -140,10 +517,10 @@ pub fn update_cell(cell: Cell, x: i32, y: i32, read_state: &GameState, write_sta
                     if rand::random() {
                         degrade = 2;
                     }
-                    write_state.write_cell(Cell::Fire{heat: heat - degrade}, dx, dy, true);
+                    write_state.write_cell(Cell::Fire{heat: heat - degrade, shade}, dx, dy, true);
                 }
                 _ => {
-                    write_state.write_cell(Cell::Fire{heat: heat - 1}, x, y, true);
+                    write_state.write_cell(Cell::Fire{heat: heat - 1, shade}, x, y, true);
                     update_if_on_boundary(x, y, write_state);
                 }
             }
@@ -151,8 +528,9 @@ pub fn update_cell(cell: Cell, x: i32, y: i32, read_state: &GameState, write_sta
         Cell::Seed => {
             match gravity(Cell::Seed, x, y, read_state, write_state) {
                 GravityResult::OnGround => {
-                    match read_state.read_cell(x, y + 1) {
-                        Cell::Sand => {
+                    let (bx, by) = step(x, y, down);
+                    match read_state.read_cell(bx, by) {
+                        Cell::Sand{..} => {
                             write_state.write_cell(Cell::Vine{growth: 50, grown: false}, x, y, true);
                         },
                         _ => {
@@ -160,7 +538,7 @@ pub fn update_cell(cell: Cell, x: i32, y: i32, read_state: &GameState, write_sta
                         }
                     }
                 },
-                GravityResult::Falling => {}
+                GravityResult::Falling | GravityResult::Floating => {}
             }
         },
         Cell::Vine{growth, grown} => {
@@ -187,10 +565,13 @@ pub fn update_cell(cell: Cell, x: i32, y: i32, read_state: &GameState, write_sta
                 return;
             }           
 
-            let (dx, mut dy) = random_dir(x, y);
-            if dy > y {
-                dy = y;
-            }  
+            // never grow downwards
+            let (mut dx, mut dy) = random_dir(x, y);
+            let (gx, gy) = DIRECTIONS[down];
+            if (dx - x) * gx + (dy - y) * gy > 0 {
+                dx -= gx;
+                dy -= gy;
+            }
             match read_state.read_cell(dx, dy) {
                 Cell::Air => {                  
                     write_state.write_cell(Cell::Vine{growth: growth - 1, grown: false}, dx, dy, true);
@@ -202,7 +583,7 @@ pub fn update_cell(cell: Cell, x: i32, y: i32, read_state: &GameState, write_sta
                 }
             }
         },
-        Cell::Water{dx} => {
+        Cell::Water{dx, shade} => {
             if dissolve_in_acid(x, y, read_state, write_state) == AcidResult::Dissolved {
                 return;
             } 
@@ -211,29 +592,30 @@ pub fn update_cell(cell: Cell, x: i32, y: i32, read_state: &GameState, write_sta
                 GravityResult::OnGround => {
 
                     // FALL THROUGH
-                    match read_state.read_cell(x, y - 1) {
-                        Cell::Sand => {
-                            write_state.write_cell(Cell::Water{dx: 0}, x, y - 1, true);
-                            write_state.write_cell(Cell::Sand, x, y, true);
+                    let (ax, ay) = step(x, y, up);
+                    match read_state.read_cell(ax, ay) {
+                        sand @ Cell::Sand{..} => {
+                            write_state.write_cell(Cell::Water{dx: 0, shade}, ax, ay, true);
+                            write_state.write_cell(*sand, x, y, true);
                             return;
                         },
                         _ => {}
                     }
 
                     // MOVE SIDEWAYS
-                    let mut sideways = dx + x;
+                    let mut delta = dx;
                     if dx == 0 {
-                        sideways = random_axis(x);
+                        delta = random_axis(0);
                     }
-                    let delta = sideways - x;
-                    let inverse_sideways =  x - delta;
-                    if read_state.is_empty(sideways, y) && write_state.is_empty(sideways, y) {
-                        write_state.mark_block_dirty(inverse_sideways, y);
+                    let (sx, sy) = DIRECTIONS[(down + 2) % 8];
+                    let sideways = (x + sx * delta, y + sy * delta);
+                    if read_state.is_empty(sideways.0, sideways.1) && write_state.is_empty(sideways.0, sideways.1) {
+                        write_state.mark_block_dirty(x - sx * delta, y - sy * delta);
                         write_state.write_cell(Cell::Air, x, y, false);
-                        write_state.write_cell(Cell::Water{dx: delta}, sideways, y, true);
+                        write_state.write_cell(Cell::Water{dx: delta, shade}, sideways.0, sideways.1, true);
                     }
                     else {
-                        write_state.write_cell(Cell::Water{dx: 0}, x, y, true);
+                        write_state.write_cell(Cell::Water{dx: 0, shade}, x, y, true);
                     }
                 },
                 _ => {}
@@ -245,7 +627,8 @@ pub fn update_cell(cell: Cell, x: i32, y: i32, read_state: &GameState, write_sta
                     if t > 0 {
                         write_state.write_cell(Cell::Air, x, y, false);
                     }
-                    write_state.mark_block_dirty(x, y + 1);
+                    let (bx, by) = step(x, y, down);
+                    write_state.mark_block_dirty(bx, by);
                 },
                 _ => {}
             }
@@ -259,7 +642,7 @@ pub fn update_cell(cell: Cell, x: i32, y: i32, read_state: &GameState, write_sta
             } 
 
             if (lx, ly) != (-1, -1) {
-                let (dx, dy) = (random_axis(x), y - 1);         
+                let (dx, dy) = step(x, y, spread(up));
                 
                 match read_state.read_cell(lx, ly) {
                     Cell::Air | Cell::Rocket{..} | Cell::Fire{..} => {},
@@ -272,7 +655,7 @@ pub fn update_cell(cell: Cell, x: i32, y: i32, read_state: &GameState, write_sta
             else {
                 match gravity(Cell::Rocket{last_pos: (lx, ly), i}, x, y, read_state, write_state) {
                     GravityResult::OnGround => {
-                        write_state.write_cell(Cell::Rocket{last_pos: (x, y + 1), i: i}, x, y, true);
+                        write_state.write_cell(Cell::Rocket{last_pos: step(x, y, down), i: i}, x, y, true);
                     }
                     _ => {}
                 }
@@ -300,20 +683,61 @@ pub fn update_cell(cell: Cell, x: i32, y: i32, read_state: &GameState, write_sta
         Cell::Ice => {
             match burn_near_fire(x, y, read_state, write_state) {
                 FireResult::Burnt => {
-                    write_state.write_cell(Cell::Water{dx: 0}, x, y, true);
+                    write_state.write_cell(Cell::Water{dx: 0, shade: 0}.fresh(), x, y, true);
                 },
                 FireResult::Unaffected => {
                     write_state.write_cell(Cell::Ice, x, y, false);
                 }
             }
+        },
+        Cell::Tap{material, t} => {
+            // stays awake so it keeps counting down
+            if t <= 0 {
+                let (bx, by) = step(x, y, down);
+                emit(MATERIALS[material], bx, by, read_state, write_state);
+                write_state.write_cell(Cell::Tap{material, t: TAP_INTERVAL}, x, y, true);
+            }
+            else {
+                write_state.write_cell(Cell::Tap{material, t: t - 1}, x, y, true);
+            }
+            update_if_on_boundary(x, y, write_state);
+        },
+        Cell::Void => {
+            // anything touching a void removes itself, so just keep the neighbours awake
+            write_state.write_cell(Cell::Void, x, y, true);
+            update_if_on_boundary(x, y, write_state);
+        },
+        Cell::Cloner{material: None} => {
+            write_state.write_cell(Cell::Cloner{material: first_touching(x, y, read_state)}, x, y, true);
+            update_if_on_boundary(x, y, write_state);
+        },
+        Cell::Cloner{material: Some(material)} => {
+            let (dx, dy) = random_dir(x, y);
+            emit(MATERIALS[material], dx, dy, read_state, write_state);
+            write_state.write_cell(cell, x, y, true);
+            update_if_on_boundary(x, y, write_state);
+        },
+        Cell::Custom{id, state} => {
+            let _ = with_material(id, |material| {
+                let properties = material.properties();
+                if properties.flammable && burn_near_fire(x, y, read_state, write_state) == FireResult::Burnt {
+                    return;
+                }
+                if properties.soluble && dissolve_in_acid(x, y, read_state, write_state) == AcidResult::Dissolved {
+                    return;
+                }
+                material.update(state, x, y, read_state, write_state);
+            });
         }
     }
 }
 
 #[derive(PartialEq, Eq)]
-enum GravityResult {
+pub enum GravityResult {
     OnGround,
-    Falling
+    Falling,
+    /// Zero-g, nothing to land on or fall towards
+    Floating
 }
 
 #[derive(PartialEq, Eq)]
@@ -332,7 +756,7 @@ fn destory_lineh(x: i32, y: i32, l: i32, rng: &mut ThreadRng, write_state: &mut
     for i in y - (l / 2)..y + (l / 2)
     {
         if rng.gen::<bool>() {
-            write_state.write_cell(Cell::Fire{heat: 30}, x, i, true);
+            write_state.write_cell(Cell::Fire{heat: 30, shade: 0}.fresh(), x, i, true);
         }
         else{
             write_state.write_cell(Cell::Destroyed, x, i, true);
@@ -340,7 +764,7 @@ fn destory_lineh(x: i32, y: i32, l: i32, rng: &mut ThreadRng, write_state: &mut
     }
 }
 
-fn update_if_on_boundary(x: i32, y: i32, write_state: &mut GameState) {
+pub fn update_if_on_boundary(x: i32, y: i32, write_state: &mut GameState) {
     if x % REGION_SIZE == 0 {
         write_state.mark_block_dirty(x - 1, y);
     }
@@ -355,11 +779,40 @@ fn update_if_on_boundary(x: i32, y: i32, write_state: &mut GameState) {
     }
 }
 
+fn emit(cell: Cell, x: i32, y: i32, read_state: &GameState, write_state: &mut GameState) {
+    if read_state.is_empty(x, y) && write_state.is_empty(x, y) {
+        write_state.write_cell(cell.fresh(), x, y, true);
+    }
+}
+
+fn touching_void(x: i32, y: i32, read_state: &GameState) -> bool {
+    for dx in -1..=1 {
+        for dy in -1..=1 {
+            if read_state.read_cell(x + dx, y + dy) == &Cell::Void {
+                return true;
+            }
+        }
+    }
+    false
+}
+
+fn first_touching(x: i32, y: i32, read_state: &GameState) -> Option<usize> {
+    for dx in -1..=1 {
+        for dy in -1..=1 {
+            let material = material_index(*read_state.read_cell(x + dx, y + dy));
+            if material.is_some() {
+                return material;
+            }
+        }
+    }
+    None
+}
+
 fn burn_near_fire(x: i32, y: i32, read_state: &GameState, write_state: &mut GameState) -> FireResult{
     let (dx, dy) = random_dir(x, y);
     match read_state.read_cell(dx, dy) {
         Cell::Fire{..} => {
-                write_state.write_cell(Cell::Fire{heat: 30}, x, y, true);
+                write_state.write_cell(Cell::Fire{heat: 30, shade: 0}.fresh(), x, y, true);
                 FireResult::Burnt
         },
         _ => {
@@ -381,28 +834,43 @@ fn dissolve_in_acid(x: i32, y: i32, read_state: &GameState, write_state: &mut Ga
     }
 }
 
-fn gravity(cell: Cell, x: i32, y: i32, read_state: &GameState, write_state: &mut GameState) ->  GravityResult{
-    let new_y = y + 1;
-    let choices = vec![-1, 0, 1];
-    let sideways = choices.choose(&mut thread_rng()).unwrap();
-    let new_x = x + sideways;
-    let height = (read_state.size as i32) - 1;
-    if  new_y <= height && read_state.is_empty(x, new_y) {
-        write_state.write_cell(cell, x, new_y, true);
-        if x % REGION_SIZE == 0 || y % REGION_SIZE == 0 {
-            write_state.mark_block_dirty(x - sideways, y - 1);
+pub fn gravity(cell: Cell, x: i32, y: i32, read_state: &GameState, write_state: &mut GameState) ->  GravityResult{
+    let down = match read_state.gravity.fall() {
+        Some(down) => down,
+        None => {
+            // zero-g, everything floats where it is
+            write_state.write_cell(cell, x, y, false);
+            return GravityResult::Floating;
+        }
+    };
+    let sideways = spread(down);
+    let steps = read_state.gravity.steps();
+    let mut pos = (x, y);
+    for _ in 0..steps.max(1) {
+        let (new_x, new_y) = step(pos.0, pos.1, down);
+        let (side_x, side_y) = step(pos.0, pos.1, sideways);
+        if read_state.is_empty(new_x, new_y) {
+            pos = (new_x, new_y);
+        }
+        else if read_state.is_empty(side_x, side_y) {
+            pos = (side_x, side_y);
+        }
+        else {
+            break;
         }
-        GravityResult::Falling
-    }
-    else if new_y <= height && new_x >= 0 && new_x <= height && read_state.is_empty(new_x, new_y) {
-         write_state.write_cell(cell, new_x, new_y, true);  
-         if x & REGION_SIZE == 0 || y % REGION_SIZE == 0 {
-            write_state.mark_block_dirty(x - sideways, y - 1);
-         }
-         GravityResult::Falling
     }
-    else {
+    if pos == (x, y) {
         write_state.write_cell(cell, x, y, false);
         GravityResult::OnGround
     }
-}
\ No newline at end of file
+    else if steps == 0 {
+        // too weak to move this tick, but not resting either
+        write_state.write_cell(cell, x, y, true);
+        GravityResult::Falling
+    }
+    else {
+        write_state.write_cell(cell, pos.0, pos.1, true);
+        update_if_on_boundary(x, y, write_state);
+        GravityResult::Falling
+    }
+}
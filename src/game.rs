@@ -1,5 +1,8 @@
-use std::collections::HashMap;
-use sdl2::render::Texture;
+use std::collections::{HashMap, HashSet};
+use std::f32::consts::PI;
+use std::time::Duration;
+use rand::prelude::*;
+use sdl2::pixels::Color;
 use sdl2::rect::Rect;
 
 use crate::cells::{Cell, Spawner, update_cell};
@@ -38,19 +41,154 @@ impl CellBlock {
     fn clear(&mut self) {
         self.cells.clear();
     }
+
+    fn live_cells(&self) -> usize {
+        self.cells.values().filter(|c| **c != Cell::Air).count()
+    }
+}
+
+/// What happens to cells that cross an edge of the world
+#[derive(Copy, Clone, Eq, PartialEq, Debug)]
+pub enum Edge {
+    /// Solid, nothing can pass
+    Wall,
+    /// Cells come back in from the opposite edge
+    Wrap,
+    /// Cells fall out of the world and are removed
+    Open
+}
+
+#[derive(Copy, Clone, Eq, PartialEq, Debug)]
+pub struct Boundary {
+    pub left: Edge,
+    pub right: Edge,
+    pub top: Edge,
+    pub bottom: Edge,
+}
+
+impl Boundary {
+
+    pub fn new(edge: Edge) -> Self {
+        Boundary {
+            left: edge,
+            right: edge,
+            top: edge,
+            bottom: edge,
+        }
+    }
+
+    /// Cycle through the presets, starting over from walls if this isn't one of them
+    pub fn next(&self) -> Self {
+        let presets = [
+            Boundary::new(Edge::Wall),
+            Boundary::new(Edge::Wrap),
+            // continuous waterfall
+            Boundary { left: Edge::Wall, right: Edge::Wall, top: Edge::Wrap, bottom: Edge::Wrap },
+            Boundary::new(Edge::Open),
+        ];
+        match presets.iter().position(|b| b == self) {
+            Some(i) => presets[(i + 1) % presets.len()],
+            None => presets[0]
+        }
+    }
+}
+
+/// Neighbour offsets, clockwise starting from the right
+pub const DIRECTIONS: [(i32, i32); 8] = [(1, 0), (1, 1), (0, 1), (-1, 1), (-1, 0), (-1, -1), (0, -1), (1, -1)];
+
+#[derive(Copy, Clone, PartialEq, Debug)]
+pub struct Gravity {
+    pub x: f32,
+    pub y: f32,
+    /// Cells fallen per tick, zero for no gravity at all
+    pub strength: f32,
+}
+
+impl Gravity {
+
+    pub fn new(x: f32, y: f32, strength: f32) -> Self {
+        Gravity {
+            x,
+            y,
+            strength,
+        }
+    }
+
+    /// Angle of the gravity vector in eighths of a turn, matching the order of `DIRECTIONS`
+    fn angle(&self) -> f32 {
+        self.y.atan2(self.x).rem_euclid(2.0 * PI) / (PI / 4.0)
+    }
+
+    /// The direction closest to the gravity vector
+    pub fn down(&self) -> usize {
+        self.angle().round() as usize % 8
+    }
+
+    /// Opposite of down, for anything that rises
+    pub fn up(&self) -> usize {
+        (self.down() + 4) % 8
+    }
+
+    /// The direction to fall this tick, vectors between two directions pick one of them proportionally
+    pub fn fall(&self) -> Option<usize> {
+        if self.strength <= 0.0 || (self.x == 0.0 && self.y == 0.0) {
+            return None;
+        }
+        let angle = self.angle();
+        let mut dir = angle.floor();
+        if thread_rng().gen::<f32>() < angle - dir {
+            dir += 1.0;
+        }
+        Some(dir as usize % 8)
+    }
+
+    /// How many cells to fall this tick, fractional strengths fall some of the time
+    pub fn steps(&self) -> i32 {
+        let whole = self.strength.floor();
+        if thread_rng().gen::<f32>() < self.strength - whole {
+            return whole as i32 + 1;
+        }
+        whole as i32
+    }
+
+    /// Point at the next of the eight directions, clockwise
+    pub fn rotate(&self) -> Self {
+        let (x, y) = DIRECTIONS[(self.down() + 1) % 8];
+        Gravity::new(x as f32, y as f32, self.strength)
+    }
+
+    /// Cycle through the strength presets, including zero-g
+    pub fn next_strength(&self) -> Self {
+        let presets = [1.0, 2.0, 0.0, 0.5];
+        let i = presets.iter().position(|s| *s == self.strength).map_or(0, |i| i + 1);
+        Gravity::new(self.x, self.y, presets[i % presets.len()])
+    }
 }
 
-pub struct GameState<'a> {
+enum Location {
+    Inside(i32, i32),
+    Wall,
+    Outside
+}
+
+pub struct GameState {
     pub size: i32,
     pub blocks : HashMap<(i32, i32), CellBlock>,
-    texture: Texture<'a>,
+    pub boundary: Boundary,
+    pub gravity: Gravity,
+    /// Blocks that were asleep before the last update and were woken by writes from their neighbours
+    pub woken: HashSet<(i32, i32)>,
+    /// RGB24 picture of the world, kept up to date by `write_cell`
+    pixels: Vec<u8>,
+    /// Bounds of the pixels changed since the last `take_changed`, inclusive
+    changed: Option<(i32, i32, i32, i32)>,
 }
 
 pub const REGION_SIZE: i32 = 16;
 
-impl<'a> GameState<'a> {
+impl GameState {
 
-    pub fn new(size: i32, texture: Texture<'a>) -> Self {
+    pub fn new(size: i32) -> Self {
         let mut blocks = HashMap::new();
         let block_num = size / REGION_SIZE;
         for x in 0..block_num {
@@ -61,7 +199,31 @@ impl<'a> GameState<'a> {
         GameState {
             size,
             blocks,
-            texture,
+            boundary: Boundary::new(Edge::Wall),
+            gravity: Gravity::new(0.0, 1.0, 1.0),
+            woken: HashSet::new(),
+            pixels: vec![0u8; (size * size * 3) as usize],
+            changed: Some((0, 0, size - 1, size - 1)),
+        }
+    }
+
+    /// Resolve world coordinates against the boundary
+    fn locate(&self, x: i32, y: i32) -> Location {
+        // anything inside the world is unaffected by wrapping
+        let edge_x = match (x < 0, x >= self.size) {
+            (true, _) => self.boundary.left,
+            (_, true) => self.boundary.right,
+            _ => Edge::Wrap
+        };
+        let edge_y = match (y < 0, y >= self.size) {
+            (true, _) => self.boundary.top,
+            (_, true) => self.boundary.bottom,
+            _ => Edge::Wrap
+        };
+        match (edge_x, edge_y) {
+            (Edge::Wall, _) | (_, Edge::Wall) => Location::Wall,
+            (Edge::Open, _) | (_, Edge::Open) => Location::Outside,
+            _ => Location::Inside(x.rem_euclid(self.size), y.rem_euclid(self.size))
         }
     }
 
@@ -70,7 +232,12 @@ impl<'a> GameState<'a> {
     }
 
     pub fn mark_block_dirty(&mut self, x: i32, y: i32) {
-        match self.get_block_mut((x) / REGION_SIZE, (y) / REGION_SIZE) {
+        let (x, y) = match self.locate(x, y) {
+            Location::Inside(x, y) => (x, y),
+            _ => return
+        };
+        let (bx, by) = ((x) / REGION_SIZE, (y) / REGION_SIZE);
+        match self.blocks.get_mut(&(bx, by)) {
             Some(b) => {
                 b.dirty = true;
             },
@@ -80,16 +247,52 @@ impl<'a> GameState<'a> {
 
     pub fn reset_block(&mut self, x: i32, y: i32) {
         // TODO index blocks based on global x/y instead of block x/y?
-        let r = Rect::new(x * REGION_SIZE,  y * REGION_SIZE, REGION_SIZE as u32, REGION_SIZE as u32);
-        self.texture.update(r, &vec![255u8; 16 * 16 * 3], 16 * 3);
+        let c = render::get_cell_color(Cell::Air);
+        for iy in 0..REGION_SIZE {
+            for ix in 0..REGION_SIZE {
+                self.set_pixel(x * REGION_SIZE + ix, y * REGION_SIZE + iy, c);
+            }
+        }
         self.blocks.get_mut(&(x, y)).unwrap().clear();
     }
 
-    pub fn get_tex(&mut self) -> &mut Texture<'a> {
-        &mut self.texture
+    /// Number of non-air cells in the world
+    pub fn live_cells(&self) -> usize {
+        self.blocks.values().map(|b| b.live_cells()).sum()
+    }
+
+    pub fn pixels(&self) -> &[u8] {
+        &self.pixels
+    }
+
+    fn set_pixel(&mut self, x: i32, y: i32, c: Color) {
+        let i = ((y * self.size + x) * 3) as usize;
+        self.pixels[i..i + 3].copy_from_slice(&[c.r, c.g, c.b]);
+        self.changed = match self.changed {
+            Some((x0, y0, x1, y1)) => Some((x0.min(x), y0.min(y), x1.max(x), y1.max(y))),
+            None => Some((x, y, x, y))
+        };
+    }
+
+    /// Start from another state's picture, along with any changes to it that haven't been taken yet
+    pub fn copy_pixels(&mut self, other: &GameState) {
+        self.pixels.copy_from_slice(&other.pixels);
+        self.changed = other.changed;
+    }
+
+    /// Area of the picture that has changed since this was last called
+    pub fn take_changed(&mut self) -> Option<Rect> {
+        self.changed.take().map(|(x0, y0, x1, y1)| {
+            Rect::new(x0, y0, (x1 - x0 + 1) as u32, (y1 - y0 + 1) as u32)
+        })
     }
 
     pub fn is_empty(&self, x: i32, y: i32) -> bool {
+        let (x, y) = match self.locate(x, y) {
+            Location::Inside(x, y) => (x, y),
+            Location::Wall => return false,
+            Location::Outside => return true
+        };
         let bx = x / REGION_SIZE;
         let by = y / REGION_SIZE;
         let ix = x % REGION_SIZE;
@@ -110,6 +313,10 @@ impl<'a> GameState<'a> {
     }
 
     pub fn read_cell(&self, x: i32, y: i32) -> &Cell {
+        let (x, y) = match self.locate(x, y) {
+            Location::Inside(x, y) => (x, y),
+            _ => return &Cell::Air
+        };
         let bx = x / REGION_SIZE;
         let by = y / REGION_SIZE;
         let ix = x % REGION_SIZE;
@@ -130,6 +337,11 @@ impl<'a> GameState<'a> {
     }
 
     pub fn write_cell(&mut self, cell: Cell, x: i32, y: i32, dirty: bool) {
+        // cells written past an open edge are gone
+        let (x, y) = match self.locate(x, y) {
+            Location::Inside(x, y) => (x, y),
+            _ => return
+        };
         let bx = x / REGION_SIZE;
         let by = y / REGION_SIZE;
         let ix = x % REGION_SIZE;
@@ -145,14 +357,7 @@ impl<'a> GameState<'a> {
                     b.dirty = dirty;
                 }
                 b.set_cell(cell, ix, iy);
-                match cell {
-                    Cell::Air => {},
-                    _ => {
-                        let r = Rect::new( x, y, 1, 1);
-                        let c = render::get_cell_color(cell);
-                        self.texture.update(r, &vec![c.r, c.g, c.b], 3);
-                    }
-                }
+                self.set_pixel(x, y, render::get_cell_color(cell));
             },
             None => {}
         }         
@@ -162,8 +367,85 @@ impl<'a> GameState<'a> {
         for (_, block) in self.blocks.iter_mut() {
             block.clear();
         }
-        let r = Rect::new(0,  0, render::MAP_SIZE as u32, render::MAP_SIZE as u32);
-        self.texture.update(r, &vec![0u8; (render::MAP_SIZE * render::MAP_SIZE * 3) as usize], 256 * 3);
+        let c = render::get_cell_color(Cell::Air);
+        for p in self.pixels.chunks_mut(3) {
+            p.copy_from_slice(&[c.r, c.g, c.b]);
+        }
+        self.changed = Some((0, 0, self.size - 1, self.size - 1));
+    }
+}
+
+/// Time between simulation ticks at normal speed
+pub const TICK: Duration = Duration::from_millis(16);
+
+/// Stop fast forward from falling further and further behind
+const MAX_TICKS_PER_FRAME: u32 = 16;
+
+const SPEEDS: [f32; 7] = [0.125, 0.25, 0.5, 1.0, 2.0, 4.0, 8.0];
+
+/// Decides how many fixed length ticks to run each frame
+pub struct Clock {
+    accumulator: Duration,
+    speed: usize,
+    paused: bool,
+    step: bool,
+}
+
+impl Clock {
+
+    pub fn new() -> Self {
+        Clock {
+            accumulator: Duration::from_secs(0),
+            speed: 3,
+            paused: false,
+            step: false,
+        }
+    }
+
+    /// Ticks to run for a frame that took `elapsed`
+    pub fn advance(&mut self, elapsed: Duration) -> u32 {
+        if self.paused {
+            let ticks = self.step as u32;
+            self.step = false;
+            return ticks;
+        }
+        self.accumulator += elapsed.mul_f32(self.speed());
+        let mut ticks = 0;
+        while self.accumulator >= TICK {
+            self.accumulator -= TICK;
+            ticks += 1;
+        }
+        if ticks > MAX_TICKS_PER_FRAME {
+            self.accumulator = Duration::from_secs(0);
+            ticks = MAX_TICKS_PER_FRAME;
+        }
+        ticks
+    }
+
+    pub fn toggle_pause(&mut self) {
+        self.paused = !self.paused;
+        self.accumulator = Duration::from_secs(0);
+    }
+
+    /// Run a single tick next frame, only while paused
+    pub fn step(&mut self) {
+        self.step = self.paused;
+    }
+
+    pub fn slower(&mut self) {
+        self.speed = self.speed.saturating_sub(1);
+    }
+
+    pub fn faster(&mut self) {
+        self.speed = (self.speed + 1).min(SPEEDS.len() - 1);
+    }
+
+    pub fn speed(&self) -> f32 {
+        SPEEDS[self.speed]
+    }
+
+    pub fn is_paused(&self) -> bool {
+        self.paused
     }
 }
 
@@ -185,6 +467,7 @@ pub fn update(read_state: &GameState, write_state: &mut GameState, spawner: &mut
     for (_, block) in write_state.blocks.iter_mut() {
         block.dirty = false;
     }
+    write_state.woken.clear();
 
     for (pos, block) in read_state.blocks.iter() {
         let block_offset = (pos.0 * REGION_SIZE, pos.1 * REGION_SIZE);
@@ -196,5 +479,13 @@ pub fn update(read_state: &GameState, write_state: &mut GameState, spawner: &mut
         }            
     }
     
+    // anything dirty now that wasn't before was woken by another block, checked before painting
+    // so the brush doesn't count
+    for (pos, block) in write_state.blocks.iter() {
+        if block.dirty && !read_state.blocks.get(pos).is_some_and(|b| b.dirty) {
+            write_state.woken.insert(*pos);
+        }
+    }
+
     spawner.spawn(write_state);
 }
@@ -0,0 +1,174 @@
+use std::fs::{self, File};
+use std::io::{self, BufWriter, Write};
+use std::path::Path;
+
+use crate::cells::{Cell, RadialSpawner, material_index};
+use crate::capture::{scaled_pixels, write_png};
+use crate::game::{self, GameState, TICK};
+use crate::render::MAP_SIZE;
+use crate::script;
+
+#[derive(Copy, Clone, PartialEq, Debug)]
+pub enum Format {
+    Png,
+    Y4m,
+}
+
+/// What to simulate and where to put the frames, see `Options::parse`
+pub struct Options {
+    pub ticks: u32,
+    pub every: u32,
+    pub scale: u32,
+    pub format: Format,
+    /// Directory for numbered PNGs, or the y4m file with `-` meaning stdout
+    pub out: String,
+    pub demo: bool,
+}
+
+const USAGE: &str = "usage: sand --headless [--ticks N] [--every N] [--scale N] [--format png|y4m] [--out PATH] [--empty]";
+
+impl Options {
+
+    pub fn parse(args: &[String]) -> Result<Options, String> {
+        let mut options = Options {
+            ticks: 600,
+            every: 1,
+            scale: 1,
+            format: Format::Png,
+            out: String::new(),
+            demo: true,
+        };
+        let mut args = args.iter();
+        while let Some(arg) = args.next() {
+            match arg.as_str() {
+                "--headless" => {},
+                "--empty" => options.demo = false,
+                "--ticks" => options.ticks = number(args.next())?,
+                "--every" => options.every = number(args.next())?.max(1),
+                "--scale" => options.scale = number(args.next())?.max(1),
+                "--out" => options.out = args.next().ok_or(USAGE)?.clone(),
+                "--format" => {
+                    options.format = match args.next().map(|f| f.as_str()) {
+                        Some("png") => Format::Png,
+                        Some("y4m") => Format::Y4m,
+                        _ => return Err(USAGE.to_owned())
+                    }
+                },
+                _ => return Err(format!("unknown argument {}\n{}", arg, USAGE))
+            }
+        }
+        if options.out.is_empty() {
+            options.out = match options.format {
+                Format::Png => "frames".to_owned(),
+                Format::Y4m => "sand.y4m".to_owned()
+            };
+        }
+        Ok(options)
+    }
+}
+
+fn number(arg: Option<&String>) -> Result<u32, String> {
+    arg.and_then(|a| a.parse().ok()).ok_or_else(|| USAGE.to_owned())
+}
+
+/// Taps of sand and water pouring onto stone ledges, something to look at without any input
+fn demo_scene(state: &mut GameState) {
+    let size = state.size;
+    for x in 0..size {
+        state.write_cell(Cell::Stone, x, size - 1, true);
+    }
+    for i in 0..3 {
+        let y = size / 4 + i * size / 5;
+        let (x0, x1) = match i % 2 {
+            0 => (size / 8, size / 2),
+            _ => (size / 2, size - size / 8)
+        };
+        for x in x0..x1 {
+            state.write_cell(Cell::Stone, x, y, true);
+        }
+    }
+    let taps = [
+        (size / 4, Cell::Sand{shade: 0}),
+        (size / 3, Cell::Water{dx: 0, shade: 0}),
+        (size * 3 / 4, Cell::Sand{shade: 0}),
+    ];
+    for (x, cell) in taps.iter() {
+        if let Some(material) = material_index(*cell) {
+            state.write_cell(Cell::Tap{material, t: 0}, *x, 4, true);
+        }
+    }
+}
+
+/// Writes each frame as YUV 4:4:4, which any y4m reader can take without chroma subsampling
+struct Y4mWriter {
+    out: Box<dyn Write>,
+}
+
+impl Y4mWriter {
+
+    fn new(path: &str, side: u32, every: u32) -> Result<Y4mWriter, String> {
+        let mut out: Box<dyn Write> = match path {
+            "-" => Box::new(BufWriter::new(io::stdout())),
+            _ => Box::new(BufWriter::new(File::create(path).map_err(|e| e.to_string())?))
+        };
+        let frame_ms = TICK.as_millis() as u32 * every;
+        writeln!(out, "YUV4MPEG2 W{} H{} F1000:{} Ip A1:1 C444", side, side, frame_ms).map_err(|e| e.to_string())?;
+        Ok(Y4mWriter { out })
+    }
+
+    fn write_frame(&mut self, pixels: &[u8]) -> Result<(), String> {
+        let count = pixels.len() / 3;
+        let mut planes = vec![0u8; count * 3];
+        for (i, p) in pixels.chunks(3).enumerate() {
+            let (r, g, b) = (p[0] as f32, p[1] as f32, p[2] as f32);
+            // BT.601 studio range
+            planes[i] = (16.0 + 0.257 * r + 0.504 * g + 0.098 * b).round() as u8;
+            planes[count + i] = (128.0 - 0.148 * r - 0.291 * g + 0.439 * b).round() as u8;
+            planes[2 * count + i] = (128.0 + 0.439 * r - 0.368 * g - 0.071 * b).round() as u8;
+        }
+        self.out.write_all(b"FRAME\n").map_err(|e| e.to_string())?;
+        self.out.write_all(&planes).map_err(|e| e.to_string())
+    }
+}
+
+/// Run the simulation with no window, writing every `every`th tick out as a frame
+pub fn run(options: &Options) -> Result<(), String> {
+    script::load_scripts(script::SCRIPT_DIR);
+
+    let mut read_state = GameState::new(MAP_SIZE);
+    let mut write_state = GameState::new(MAP_SIZE);
+    if options.demo {
+        demo_scene(&mut read_state);
+    }
+    let mut spawner = RadialSpawner::new(0, 0);
+
+    let side = MAP_SIZE as u32 * options.scale;
+    let mut y4m = match options.format {
+        Format::Png => {
+            fs::create_dir_all(&options.out).map_err(|e| e.to_string())?;
+            None
+        },
+        Format::Y4m => Some(Y4mWriter::new(&options.out, side, options.every)?)
+    };
+
+    for tick in 0..options.ticks {
+        if tick % options.every == 0 {
+            let pixels = scaled_pixels(&read_state, options.scale);
+            match y4m.as_mut() {
+                Some(writer) => writer.write_frame(&pixels)?,
+                None => {
+                    let path = Path::new(&options.out).join(format!("frame-{:05}.png", tick / options.every));
+                    write_png(&path.to_string_lossy(), &pixels, side, side)?;
+                }
+            }
+        }
+
+        write_state.copy_pixels(&read_state);
+        game::update(&read_state, &mut write_state, &mut spawner);
+        std::mem::swap(&mut read_state, &mut write_state);
+    }
+    if let Some(writer) = y4m.as_mut() {
+        writer.out.flush().map_err(|e| e.to_string())?;
+    }
+    Ok(())
+}
@@ -1,54 +1,215 @@
 use sdl2::event::Event;
-use sdl2::keyboard::Keycode;
-use crate::cells::{Cell, RadialSpawner};
-use crate::render;
+use sdl2::keyboard::{Keycode, Mod};
+use sdl2::mouse::{MouseButton, MouseWheelDirection};
+use crate::brush::{PaintMode, Tool};
+use crate::cells::{Cell, RadialSpawner, Stroke, material_index};
+use crate::material::next_material;
+use crate::game::GameState;
+use crate::render::{Camera, Palette, PaletteClick};
 
 
-pub fn update_spawner(event: Event, spawner: &mut RadialSpawner) {
+const ZOOM_STEP: f32 = 1.25;
+
+fn ctrl_held(keymod: Mod) -> bool {
+    keymod.intersects(Mod::LCTRLMOD | Mod::RCTRLMOD)
+}
+
+fn shift_held(keymod: Mod) -> bool {
+    keymod.intersects(Mod::LSHIFTMOD | Mod::RSHIFTMOD)
+}
+
+fn alt_held(keymod: Mod) -> bool {
+    keymod.intersects(Mod::LALTMOD | Mod::RALTMOD)
+}
+
+fn wheel_notches(y: i32, direction: MouseWheelDirection) -> i32 {
+    match direction {
+        MouseWheelDirection::Flipped => -y,
+        _ => y
+    }
+}
+
+pub fn update_camera(event: &Event, camera: &mut Camera, mouse: (i32, i32), keymod: Mod) {
+    match *event {
+        Event::MouseMotion{mousestate, xrel, yrel, ..} if mousestate.middle() => {
+            camera.pan(xrel, yrel);
+        },
+        Event::MouseWheel{y, direction, ..} if !ctrl_held(keymod) && camera.view.contains_point(mouse) => {
+            camera.zoom_at(mouse.0, mouse.1, ZOOM_STEP.powi(wheel_notches(y, direction)));
+        },
+        Event::KeyDown {keycode: Some(Keycode::Home), ..} => {
+            camera.reset();
+        },
+        _ => {}
+    }
+}
+
+/// Pick materials from the palette, returns whether the event was used up by it
+pub fn update_palette(event: &Event, palette: &mut Palette, spawner: &mut RadialSpawner) -> bool {
+    match *event {
+        Event::MouseButtonDown{mouse_btn, x, y, ..} => {
+            match (palette.click(x, y), mouse_btn) {
+                (PaletteClick::Missed, _) => false,
+                (PaletteClick::Select(cell), MouseButton::Right) => {
+                    spawner.set_secondary(cell);
+                    true
+                },
+                (PaletteClick::Select(cell), _) => {
+                    spawner.set_cell(cell);
+                    true
+                },
+                (PaletteClick::Page, _) => true
+            }
+        },
+        _ => false
+    }
+}
+
+/// Alt click picks up whatever's under the cursor, as it would be freshly placed
+pub fn update_eyedropper(event: &Event, spawner: &mut RadialSpawner, state: &GameState, camera: &Camera, keymod: Mod) -> bool {
+    match *event {
+        Event::MouseButtonDown{mouse_btn, x, y, ..} if alt_held(keymod) && camera.view.contains_point((x, y)) => {
+            let (wx, wy) = camera.to_world(x, y);
+            let cell = state.read_cell(wx, wy).spawned();
+            match mouse_btn {
+                MouseButton::Left => spawner.set_cell(cell),
+                MouseButton::Right => spawner.set_secondary(cell),
+                _ => return false
+            }
+            true
+        },
+        _ => false
+    }
+}
+
+pub fn update_spawner(event: Event, spawner: &mut RadialSpawner, camera: &Camera, keymod: Mod) {
     match event {
-        Event::MouseMotion{x, y, ..} => {
-            spawner.set_pos((x as f32 * render::MOUSE_RATIO) as i32, (y as f32 * render::MOUSE_RATIO) as i32);
-        }
-        Event::MouseButtonDown{..} => {
-            spawner.enable();
+        Event::MouseWheel{y, direction, ..} if ctrl_held(keymod) => {
+            let radius = spawner.brush.radius() + wheel_notches(y, direction);
+            spawner.brush.set_radius(radius);
         },
-        Event::MouseButtonUp{..} => {
-            spawner.disable();
+        Event::KeyDown {keycode: Some(Keycode::Minus), ..} if shift_held(keymod) => {
+            spawner.brush.change_density(-1);
         },
-        Event::KeyDown {keycode: Some(Keycode::Q), ..} => {
-            spawner.set_cell(Cell::Wood);
+        Event::KeyDown {keycode: Some(Keycode::Equals), ..} if shift_held(keymod) => {
+            spawner.brush.change_density(1);
         },
-        Event::KeyDown {keycode: Some(Keycode::W), ..} => {
-            spawner.set_cell(Cell::Fire{heat: 30});
+        Event::KeyDown {keycode: Some(Keycode::Minus), ..} => {
+            let radius = spawner.brush.radius() - 1;
+            spawner.brush.set_radius(radius);
         },
-        Event::KeyDown {keycode: Some(Keycode::E), ..} => {
-            spawner.set_cell(Cell::Seed);
+        Event::KeyDown {keycode: Some(Keycode::Equals), ..} => {
+            let radius = spawner.brush.radius() + 1;
+            spawner.brush.set_radius(radius);
         },
-        Event::KeyDown {keycode: Some(Keycode::R), ..} => {
-            spawner.set_cell(Cell::Water{dx: 0});
+        Event::KeyDown {keycode: Some(Keycode::Num1), ..} => {
+            spawner.tool = Tool::Freehand;
         },
-        Event::KeyDown {keycode: Some(Keycode::T), ..} => {
-            spawner.set_cell(Cell::Acid{t: 0});
+        Event::KeyDown {keycode: Some(Keycode::Num2), ..} => {
+            spawner.tool = Tool::Line;
         },
-        Event::KeyDown {keycode: Some(Keycode::Y), ..} => {
-            spawner.set_cell(Cell::Sand);
+        Event::KeyDown {keycode: Some(Keycode::Num3), ..} => {
+            spawner.tool = Tool::Rect;
         },
-        Event::KeyDown {keycode: Some(Keycode::U), ..} => {
-            spawner.set_cell(Cell::Rocket{last_pos: (-1, -1), i: -1});
+        Event::KeyDown {keycode: Some(Keycode::Num4), ..} => {
+            spawner.tool = Tool::Circle;
         },
-        Event::KeyDown {keycode: Some(Keycode::I), ..} => {
-            spawner.set_cell(Cell::Stone);
+        Event::KeyDown {keycode: Some(Keycode::Num5), ..} => {
+            spawner.tool = Tool::Fill;
         },
-        Event::KeyDown {keycode: Some(Keycode::O), ..} => {
-            spawner.set_cell(Cell::Bomb);
+        Event::KeyDown {keycode: Some(Keycode::Tab), ..} => {
+            spawner.filled = !spawner.filled;
         },
-        Event::KeyDown {keycode: Some(Keycode::P), ..} => {
-            spawner.set_cell(Cell::Ice);
+        Event::KeyDown {keycode: Some(Keycode::V), ..} => {
+            spawner.symmetry = spawner.symmetry.next();
+        },
+        Event::KeyDown {keycode: Some(Keycode::N), ..} => {
+            spawner.symmetry = spawner.symmetry.more_folds();
+        },
+        Event::KeyDown {keycode: Some(Keycode::C), ..} => {
+            spawner.centre = match ctrl_held(keymod) {
+                true => None,
+                false => Some(spawner.get_pos())
+            };
+        },
+        Event::KeyDown {keycode: Some(Keycode::X), ..} => {
+            let shape = spawner.brush.shape().next();
+            spawner.brush.set_shape(shape);
+        },
+        Event::MouseMotion{x, y, ..} => {
+            let (wx, wy) = camera.to_world(x, y);
+            spawner.set_pos(wx, wy);
+        }
+        // presses over the palette or key bindings would paint under them when zoomed in
+        Event::MouseButtonDown{x, y, ..} if !camera.view.contains_point((x, y)) => {},
+        Event::MouseButtonDown{mouse_btn: MouseButton::Left, ..} => {
+            spawner.enable(Stroke::Primary);
+        },
+        Event::MouseButtonUp{mouse_btn: MouseButton::Left, ..} => {
+            spawner.disable(Stroke::Primary);
+        },
+        Event::MouseButtonDown{mouse_btn: MouseButton::Right, ..} => {
+            spawner.enable(Stroke::Secondary);
+        },
+        Event::MouseButtonUp{mouse_btn: MouseButton::Right, ..} => {
+            spawner.disable(Stroke::Secondary);
+        },
+        Event::KeyDown {keycode: Some(Keycode::Backquote), ..} => {
+            match (ctrl_held(keymod), alt_held(keymod), shift_held(keymod)) {
+                (true, _, true) => spawner.set_filter(None),
+                (true, _, false) => spawner.mode = PaintMode::Overwrite,
+                (false, true, _) => spawner.clear_mix(),
+                (false, false, _) => spawner.set_secondary(Cell::Air)
+            }
+        },
+        Event::KeyDown {keycode: Some(Keycode::M), ..} => {
+            spawner.mode = spawner.mode.next();
+        },
+        Event::KeyDown {keycode: Some(keycode), ..} => {
+            // shift picks for the right button, ctrl picks the only material to paint over,
+            // ctrl and shift picks the only material the eraser removes,
+            // alt adds another part to a mix for the left button
+            let shift = shift_held(keymod);
+            let current = match shift {
+                true => spawner.get_secondary(),
+                false => spawner.get_cell()
+            };
+            if let Some(cell) = material_key(keycode, current) {
+                match (ctrl_held(keymod), alt_held(keymod), shift) {
+                    (true, _, true) => spawner.set_filter(Some(cell)),
+                    (true, _, false) => spawner.replace(cell),
+                    (false, true, _) => spawner.add_to_mix(cell),
+                    (false, false, true) => spawner.set_secondary(cell),
+                    (false, false, false) => spawner.set_cell(cell)
+                }
+            }
         },
         _ => {}
     }
 }
 
+/// Material picked by a key, `current` is what the key is replacing
+fn material_key(keycode: Keycode, current: Cell) -> Option<Cell> {
+    match keycode {
+        Keycode::Q => Some(Cell::Wood),
+        Keycode::W => Some(Cell::Fire{heat: 30, shade: 0}),
+        Keycode::E => Some(Cell::Seed),
+        Keycode::R => Some(Cell::Water{dx: 0, shade: 0}),
+        Keycode::T => Some(Cell::Acid{t: 0}),
+        Keycode::Y => Some(Cell::Sand{shade: 0}),
+        Keycode::U => Some(Cell::Rocket{last_pos: (-1, -1), i: -1}),
+        Keycode::I => Some(Cell::Stone),
+        Keycode::O => Some(Cell::Bomb),
+        Keycode::P => Some(Cell::Ice),
+        // taps emit whichever material was selected last
+        Keycode::A => material_index(current).map(|material| Cell::Tap{material, t: 0}),
+        Keycode::S => Some(Cell::Void),
+        Keycode::D => Some(Cell::Cloner{material: None}),
+        Keycode::Z => next_material(current),
+        _ => None
+    }
+}
+
 
 pub fn get_key_bindings() -> Vec<(String, String)> {
     vec!(
@@ -62,7 +223,49 @@ pub fn get_key_bindings() -> Vec<(String, String)> {
         ("I".to_owned(), "Stone".to_owned()),
         ("O".to_owned(), "Bomb".to_owned()),
         ("P".to_owned(), "Ice".to_owned()),
-        ("LMB".to_owned(), "Spawn".to_owned()),
+        ("A".to_owned(), "Tap".to_owned()),
+        ("S".to_owned(), "Void".to_owned()),
+        ("D".to_owned(), "Cloner".to_owned()),
+        ("Z".to_owned(), "Custom".to_owned()),
+        ("1-5".to_owned(), "Tool".to_owned()),
+        ("TAB".to_owned(), "Fill/Outline".to_owned()),
+        ("X".to_owned(), "Brush".to_owned()),
+        ("V".to_owned(), "Symmetry".to_owned()),
+        ("N".to_owned(), "Folds".to_owned()),
+        ("C".to_owned(), "Centre".to_owned()),
+        ("- =".to_owned(), "Size".to_owned()),
+        ("SH - =".to_owned(), "Spray".to_owned()),
+        ("B".to_owned(), "Edges".to_owned()),
+        ("G".to_owned(), "Tilt".to_owned()),
+        ("H".to_owned(), "Weight".to_owned()),
+        ("SPC".to_owned(), "Pause".to_owned()),
+        (".".to_owned(), "Step".to_owned()),
+        ("[ ]".to_owned(), "Speed".to_owned()),
+        ("F1".to_owned(), "Inspect".to_owned()),
+        ("F2".to_owned(), "Grid".to_owned()),
+        ("F3".to_owned(), "Blocks".to_owned()),
+        ("F4".to_owned(), "Perf".to_owned()),
+        ("F5".to_owned(), "Perf CSV".to_owned()),
+        ("F6".to_owned(), "Pixel fit".to_owned()),
+        ("F11".to_owned(), "Fullscreen".to_owned()),
+        ("F12".to_owned(), "Screenshot".to_owned()),
+        ("F10".to_owned(), "Record GIF".to_owned()),
+        ("LMB".to_owned(), "Spawn/Pick".to_owned()),
+        ("RMB".to_owned(), "Erase".to_owned()),
+        ("ALT+LMB".to_owned(), "Eyedrop".to_owned()),
+        ("SH+key".to_owned(), "Set RMB".to_owned()),
+        ("`".to_owned(), "RMB erase".to_owned()),
+        ("M".to_owned(), "Paint mode".to_owned()),
+        ("^key".to_owned(), "Replace".to_owned()),
+        ("^`".to_owned(), "Overwrite".to_owned()),
+        ("^SH+key".to_owned(), "Erase only".to_owned()),
+        ("^SH `".to_owned(), "Erase all".to_owned()),
+        ("ALT+key".to_owned(), "Mix".to_owned()),
+        ("ALT `".to_owned(), "Unmix".to_owned()),
+        ("MMB".to_owned(), "Pan".to_owned()),
+        ("WHL".to_owned(), "Zoom".to_owned()),
+        ("^WHL".to_owned(), "Size".to_owned()),
+        ("HOME".to_owned(), "View".to_owned()),
         ("DEL".to_owned(), "Clear".to_owned()),
         ("ESC".to_owned(), "Exit".to_owned())
     )
@@ -1,11 +1,9 @@
 extern crate sdl2;
 
-use std::ops::Add;
 use std::collections::HashMap;
-use sdl2::rect::Rect;
-use sdl2::pixels::Color;
-use sdl2::event::Event;
-use sdl2::keyboard::Keycode;
+use sdl2::event::{Event, WindowEvent};
+use sdl2::video::FullscreenType;
+use sdl2::keyboard::{Keycode, Mod};
 use std::time::{Instant, Duration};
 use sdl2::pixels::PixelFormatEnum;
 
@@ -14,15 +12,30 @@ mod game;
 mod cells;
 mod render;
 mod input;
+mod material;
+mod script;
+mod perf;
+mod capture;
+mod brush;
+mod headless;
 
-use cells::{Cell, RadialSpawner};
+use cells::{RadialSpawner, Spawner};
+
+// for materials registered by crates depending on sand
+pub use cells::{Cell, gravity, step, GravityResult};
+pub use game::{GameState, Gravity, DIRECTIONS};
+pub use material::{Material, Properties, register_material};
+pub use sdl2::pixels::Color;
 
 pub fn start() {
+    script::load_scripts(script::SCRIPT_DIR);
+
     let sdl_context = sdl2::init().unwrap();
     let video_subsystem = sdl_context.video().unwrap();
 
-    let window = video_subsystem.window("sand", render::SCREEN_SIZE as u32, 580 as u32)
+    let window = video_subsystem.window("sand", render::SCREEN_SIZE as u32, (render::SCREEN_SIZE + render::PALETTE_HEIGHT) as u32)
         .position_centered()
+        .resizable()
         .build()
         .unwrap();
 
@@ -31,24 +44,33 @@ pub fn start() {
 
     let texture_creator = canvas.texture_creator();
 
-    let hud = render::Hud::new(&texture_creator);
+    let ttf_context = sdl2::ttf::init().map_err(|e| e.to_string()).unwrap();
+    let hud = render::Hud::new(&texture_creator, &ttf_context);
 
-    let tex_a = texture_creator.create_texture_target(PixelFormatEnum::RGB24, render::MAP_SIZE as u32, render::MAP_SIZE as u32).map_err(|x| x.to_string()).unwrap();
-    let tex_b = texture_creator.create_texture_target(PixelFormatEnum::RGB24, render::MAP_SIZE as u32, render::MAP_SIZE as u32).map_err(|x| x.to_string()).unwrap();
+    // make room for however many rows the key bindings take up
+    let window_height = (render::SCREEN_SIZE + render::PALETTE_HEIGHT) as u32 + hud.bindings_height(render::SCREEN_SIZE as u32);
+    canvas.window_mut().set_size(render::SCREEN_SIZE as u32, window_height).unwrap();
 
-    let mut read_state = game::GameState::new(render::MAP_SIZE, tex_a);
-    let mut write_state = game::GameState::new(render::MAP_SIZE,tex_b);
+    let mut world_tex = texture_creator.create_texture_streaming(PixelFormatEnum::RGB24, render::MAP_SIZE as u32, render::MAP_SIZE as u32).map_err(|x| x.to_string()).unwrap();
 
-    let mut frame_start = Instant::now();
+    let mut read_state = game::GameState::new(render::MAP_SIZE);
+    let mut write_state = game::GameState::new(render::MAP_SIZE);
 
-    let mut frames = 0u32;
-    let mut frame_log_timer = Duration::from_secs(0);
-    let mut update_times = Vec::new();
-    let mut draw_times = Vec::new();
+    let mut frame_start = Instant::now();
+    let mut clock = game::Clock::new();
+    let mut inspect = false;
+    let mut overlay = render::BlockOverlay::new();
+    let (view, palette_area, mut hud_area) = render::layout(render::SCREEN_SIZE as u32, window_height, hud.bindings_height(render::SCREEN_SIZE as u32));
+    let mut camera = render::Camera::new(view);
+    let mut palette = render::Palette::new(palette_area);
+    let mut mouse = (0, 0);
+    let mut perf = perf::Perf::new();
+    let mut recorder: Option<capture::Recorder> = None;
 
     // TODO move?
     let mut spawner = RadialSpawner::new(5, 5);
     'running: loop {
+        let frame_elapsed = frame_start.elapsed();
         frame_start = Instant::now();
 
         for event in event_pump.poll_iter() { 
@@ -61,59 +83,177 @@ pub fn start() {
                     read_state.clear();
                     write_state.clear();
                 }
+                Event::KeyDown {keycode: Some(Keycode::B), ..} => {
+                    let boundary = read_state.boundary.next();
+                    read_state.boundary = boundary;
+                    write_state.boundary = boundary;
+                }
+                Event::KeyDown {keycode: Some(Keycode::G), ..} => {
+                    let gravity = read_state.gravity.rotate();
+                    read_state.gravity = gravity;
+                    write_state.gravity = gravity;
+                }
+                Event::KeyDown {keycode: Some(Keycode::H), ..} => {
+                    let gravity = read_state.gravity.next_strength();
+                    read_state.gravity = gravity;
+                    write_state.gravity = gravity;
+                }
+                Event::KeyDown {keycode: Some(Keycode::Space), ..} => {
+                    clock.toggle_pause();
+                }
+                Event::KeyDown {keycode: Some(Keycode::Period), ..} => {
+                    clock.step();
+                }
+                Event::KeyDown {keycode: Some(Keycode::LeftBracket), ..} => {
+                    clock.slower();
+                }
+                Event::KeyDown {keycode: Some(Keycode::RightBracket), ..} => {
+                    clock.faster();
+                }
+                Event::KeyDown {keycode: Some(Keycode::F1), ..} => {
+                    inspect = !inspect;
+                }
+                Event::KeyDown {keycode: Some(Keycode::F2), ..} => {
+                    overlay.grid = !overlay.grid;
+                }
+                Event::KeyDown {keycode: Some(Keycode::F3), ..} => {
+                    overlay.activity = !overlay.activity;
+                }
+                Event::KeyDown {keycode: Some(Keycode::F4), ..} => {
+                    perf.visible = !perf.visible;
+                }
+                Event::KeyDown {keycode: Some(Keycode::F5), ..} => {
+                    perf.toggle_csv();
+                }
+                Event::KeyDown {keycode: Some(Keycode::F6), ..} => {
+                    camera.set_integer(!camera.is_integer());
+                }
+                Event::KeyDown {keycode: Some(Keycode::F11), ..} => {
+                    let window = canvas.window_mut();
+                    let fullscreen = match window.fullscreen_state() {
+                        FullscreenType::Off => FullscreenType::Desktop,
+                        _ => FullscreenType::Off
+                    };
+                    window.set_fullscreen(fullscreen).unwrap();
+                }
+                Event::KeyDown {keycode: Some(Keycode::F12), keymod, ..} => {
+                    match capture::screenshot(&read_state, capture_scale(keymod)) {
+                        Ok(path) => println!("Saved {}", path),
+                        Err(e) => eprintln!("Screenshot failed: {}", e)
+                    }
+                }
+                Event::KeyDown {keycode: Some(Keycode::F10), keymod, ..} => {
+                    match recorder.take() {
+                        Some(r) => {
+                            println!("Saved {}", r.path());
+                            canvas.window_mut().set_title("sand").unwrap();
+                        },
+                        None => {
+                            match capture::Recorder::start(&read_state, capture_scale(keymod)) {
+                                Ok(r) => {
+                                    recorder = Some(r);
+                                    canvas.window_mut().set_title("sand (recording)").unwrap();
+                                },
+                                Err(e) => eprintln!("Recording failed: {}", e)
+                            }
+                        }
+                    }
+                }
+                Event::Window {win_event: WindowEvent::SizeChanged(w, h), ..} => {
+                    let (view, palette_area, area) = render::layout(w as u32, h as u32, hud.bindings_height(w as u32));
+                    camera.set_view(view);
+                    palette.area = palette_area;
+                    hud_area = area;
+                }
                 _ => {
-                    input::update_spawner(event, &mut spawner);
+                    if let Event::MouseMotion{x, y, ..} = event {
+                        mouse = (x, y);
+                    }
+                    if input::update_palette(&event, &mut palette, &mut spawner) {
+                        continue;
+                    }
+                    let keymod = sdl_context.keyboard().mod_state();
+                    if input::update_eyedropper(&event, &mut spawner, &read_state, &camera, keymod) {
+                        continue;
+                    }
+                    input::update_camera(&event, &mut camera, mouse, keymod);
+                    input::update_spawner(event, &mut spawner, &camera, keymod);
                 }
             }
         }
 
         // UPDATE
-        let update_start = Instant::now();
+        let ticks = clock.advance(frame_elapsed);
+        if clock.is_paused() && ticks == 0 {
+            // keep painting while paused
+            spawner.spawn(&mut read_state);
+        }
+        for _ in 0..ticks {
+            let update_start = Instant::now();
 
-        canvas.with_texture_canvas(write_state.get_tex(), |c| {
-            c.copy(read_state.get_tex(), None, None);
-            });
+            write_state.copy_pixels(&read_state);
 
-        game::update(&read_state, &mut write_state, &mut spawner);
-        update_times.push(update_start.elapsed().as_micros());
+            game::update(&read_state, &mut write_state, &mut spawner);
+            perf.record_update(update_start.elapsed());
 
-        let tmp = read_state;
-        read_state = write_state;
-        write_state = tmp;
+            let tmp = read_state;
+            read_state = write_state;
+            write_state = tmp;
+            overlay.record(&read_state);
+        }
 
         // DRAW
         canvas.clear();
         let draw_time = Instant::now();
-        canvas.copy(&read_state.get_tex(), None, Rect::new(0, 0, 512, 512)).unwrap();
-        hud.draw(&mut canvas);
+        render::upload_changes(&mut world_tex, &mut read_state);
+        let world_rect = camera.world_rect(read_state.size);
+        canvas.set_clip_rect(camera.view);
+        canvas.copy(&world_tex, None, world_rect).unwrap();
+        overlay.draw(&mut canvas, &read_state, &camera);
+        let (points, cell) = spawner.preview(read_state.size).unwrap_or_else(|| spawner.cursor(read_state.size));
+        render::draw_preview(&mut canvas, &camera, &points, cell);
+        canvas.set_clip_rect(None);
+        hud.draw_palette(&mut canvas, &palette, &spawner);
+        hud.draw_tool(&mut canvas, camera.view, &spawner, &clock);
+        hud.draw(&mut canvas, hud_area);
+        if inspect {
+            let (x, y) = spawner.get_pos();
+            hud.draw_inspector(&mut canvas, &read_state, x, y);
+        }
+        if perf.visible {
+            hud.draw_perf(&mut canvas, &perf);
+        }
 
-        draw_times.push(draw_time.elapsed().as_micros());      
+        perf.record_draw(draw_time.elapsed());
         canvas.present();
+        perf.end_frame(&read_state);
+
+        if let Some(r) = recorder.as_mut() {
+            if let Err(e) = r.capture(&read_state) {
+                eprintln!("Recording failed: {}", e);
+                recorder = None;
+                canvas.window_mut().set_title("sand").unwrap();
+            }
+        }
 
         // SLEEP
-        let mut frame_end = frame_start.elapsed();
-        frames += 1;
-        if(frame_end < Duration::from_millis(16)) {
+        let frame_end = frame_start.elapsed();
+        if frame_end < Duration::from_millis(16) {
             std::thread::sleep(Duration::from_millis(16) - frame_end);
         }
+    }
+}
 
-        // LOG
-        frame_log_timer = frame_log_timer.add(frame_start.elapsed());
-        // if frame_log_timer >= Duration::from_millis(1000) {
-        //     println!("FPS: {}", frames);
-
-        //     let mut sum : u128 =  update_times.iter().sum();
-        //     let mut avg =  sum as f64 /  update_times.len() as f64;
-        //     println!("Update: {}", avg);
-
-        //     sum = draw_times.iter().sum();
-        //     avg = sum as f64 / draw_times.len() as f64;
-        //     println!("Draw: {}", avg);
+/// Run without a window, writing frames to disk, see `headless::Options::parse` for `args`
+pub fn start_headless(args: &[String]) -> Result<(), String> {
+    let options = headless::Options::parse(args)?;
+    headless::run(&options)
+}
 
-        //     frames = 0;
-        //     frame_log_timer = Duration::from_millis(0);
-        //     update_times.clear();
-        //     draw_times.clear();
-        // }
+/// Captures are at the world's native resolution, or as big as the default window with shift held
+fn capture_scale(keymod: Mod) -> u32 {
+    if keymod.intersects(Mod::LSHIFTMOD | Mod::RSHIFTMOD) {
+        return (render::SCREEN_SIZE / render::MAP_SIZE) as u32;
     }
-}
\ No newline at end of file
+    1
+}
@@ -1,5 +1,13 @@
-use sand::start;
+use sand::{start, start_headless};
 
 fn main() {
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    if args.iter().any(|a| a == "--headless") {
+        if let Err(e) = start_headless(&args) {
+            eprintln!("{}", e);
+            std::process::exit(1);
+        }
+        return;
+    }
     start();
 }
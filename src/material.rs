@@ -0,0 +1,72 @@
+use std::sync::{Arc, RwLock};
+use sdl2::pixels::Color;
+use crate::cells::Cell;
+use crate::game::GameState;
+
+/// Built-in rules applied to a material before its own update
+#[derive(Copy, Clone, Default, Debug)]
+pub struct Properties {
+    /// Catches fire when next to fire
+    pub flammable: bool,
+    /// Dissolves when next to acid
+    pub soluble: bool,
+}
+
+/// A material defined outside of `cells::update_cell`
+///
+/// Registered materials live in the world as `Cell::Custom`, carrying a single `i32` of state
+/// between ticks.
+pub trait Material: Send + Sync {
+
+    fn name(&self) -> &str;
+
+    fn color(&self, state: i32) -> Color;
+
+    fn properties(&self) -> Properties {
+        Properties::default()
+    }
+
+    /// Same as the built-in cells, the material must write itself into `write_state` to survive the tick
+    fn update(&self, state: i32, x: i32, y: i32, read_state: &GameState, write_state: &mut GameState);
+
+}
+
+static REGISTRY: RwLock<Vec<Arc<dyn Material>>> = RwLock::new(Vec::new());
+
+/// Register a material, returning the cell to spawn it with
+pub fn register_material(material: impl Material + 'static) -> Cell {
+    let mut registry = REGISTRY.write().unwrap();
+    registry.push(Arc::new(material));
+    Cell::Custom{id: registry.len() - 1, state: 0}
+}
+
+/// Run `f` against a registered material, `None` if nothing is registered under `id`
+///
+/// The registry isn't locked while `f` runs, so materials are free to look up other materials
+/// from `update`, and to be registered from other threads.
+pub fn with_material<T>(id: usize, f: impl FnOnce(&dyn Material) -> T) -> Option<T> {
+    let material = REGISTRY.read().unwrap().get(id).cloned()?;
+    Some(f(material.as_ref()))
+}
+
+pub fn material_count() -> usize {
+    REGISTRY.read().unwrap().len()
+}
+
+/// Id of the first material registered under `name`
+pub fn find_material(name: &str) -> Option<usize> {
+    REGISTRY.read().unwrap().iter().position(|m| m.name() == name)
+}
+
+/// The registered material after `cell`, wrapping around to the first
+pub fn next_material(cell: Cell) -> Option<Cell> {
+    let count = REGISTRY.read().unwrap().len();
+    if count == 0 {
+        return None;
+    }
+    let id = match cell {
+        Cell::Custom{id, ..} => (id + 1) % count,
+        _ => 0
+    };
+    Some(Cell::Custom{id, state: 0})
+}
@@ -0,0 +1,134 @@
+use std::fs::File;
+use std::io::Write;
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+
+use crate::game::GameState;
+
+/// Min, average and max of a second's worth of timings, in milliseconds
+#[derive(Copy, Clone, Default)]
+pub struct Timing {
+    pub min: f64,
+    pub avg: f64,
+    pub max: f64,
+}
+
+impl Timing {
+
+    fn from_micros(times: &[u128]) -> Self {
+        if times.is_empty() {
+            return Timing::default();
+        }
+        let ms: Vec<f64> = times.iter().map(|t| *t as f64 / 1000.0).collect();
+        Timing {
+            min: ms.iter().cloned().fold(f64::MAX, f64::min),
+            avg: ms.iter().sum::<f64>() / ms.len() as f64,
+            max: ms.iter().cloned().fold(0.0, f64::max),
+        }
+    }
+}
+
+/// Everything measured over the last second
+#[derive(Copy, Clone, Default)]
+pub struct Report {
+    pub fps: u32,
+    pub ticks: u32,
+    pub update: Timing,
+    pub draw: Timing,
+    pub dirty_blocks: usize,
+    pub blocks: usize,
+    pub live_cells: usize,
+}
+
+pub struct Perf {
+    pub visible: bool,
+    pub report: Report,
+    csv: Option<File>,
+    started: Instant,
+    second_start: Instant,
+    frames: u32,
+    update_times: Vec<u128>,
+    draw_times: Vec<u128>,
+}
+
+impl Perf {
+
+    pub fn new() -> Self {
+        Perf {
+            visible: false,
+            report: Report::default(),
+            csv: None,
+            started: Instant::now(),
+            second_start: Instant::now(),
+            frames: 0,
+            update_times: Vec::new(),
+            draw_times: Vec::new(),
+        }
+    }
+
+    pub fn record_update(&mut self, time: Duration) {
+        self.update_times.push(time.as_micros());
+    }
+
+    pub fn record_draw(&mut self, time: Duration) {
+        self.draw_times.push(time.as_micros());
+    }
+
+    /// Call once per frame, rolls the timings up into a report every second
+    pub fn end_frame(&mut self, state: &GameState) {
+        self.frames += 1;
+        if self.second_start.elapsed() < Duration::from_secs(1) {
+            return;
+        }
+
+        self.report = Report {
+            fps: self.frames,
+            ticks: self.update_times.len() as u32,
+            update: Timing::from_micros(&self.update_times),
+            draw: Timing::from_micros(&self.draw_times),
+            dirty_blocks: state.blocks.values().filter(|b| b.dirty).count(),
+            blocks: state.blocks.len(),
+            live_cells: state.live_cells(),
+        };
+        self.write_csv();
+
+        self.frames = 0;
+        self.second_start = Instant::now();
+        self.update_times.clear();
+        self.draw_times.clear();
+    }
+
+    /// Start or stop logging each report to `perf-<unix time>.csv`
+    pub fn toggle_csv(&mut self) {
+        if self.csv.take().is_some() {
+            return;
+        }
+        let now = SystemTime::now().duration_since(UNIX_EPOCH).map(|d| d.as_secs()).unwrap_or(0);
+        let path = format!("perf-{}.csv", now);
+        match File::create(&path) {
+            Ok(mut file) => {
+                let _ = writeln!(file, "time,fps,ticks,update_min_ms,update_avg_ms,update_max_ms,draw_min_ms,draw_avg_ms,draw_max_ms,dirty_blocks,live_cells");
+                println!("Logging performance to {}", path);
+                self.csv = Some(file);
+            },
+            Err(e) => {
+                eprintln!("{}: {}", path, e);
+            }
+        }
+    }
+
+    pub fn is_logging(&self) -> bool {
+        self.csv.is_some()
+    }
+
+    fn write_csv(&mut self) {
+        let r = self.report;
+        let time = self.started.elapsed().as_secs_f64();
+        if let Some(file) = self.csv.as_mut() {
+            let _ = writeln!(file, "{:.1},{},{},{:.3},{:.3},{:.3},{:.3},{:.3},{:.3},{},{}",
+                time, r.fps, r.ticks,
+                r.update.min, r.update.avg, r.update.max,
+                r.draw.min, r.draw.avg, r.draw.max,
+                r.dirty_blocks, r.live_cells);
+        }
+    }
+}
@@ -1,20 +1,170 @@
-use rand::prelude::*;
 use sdl2::pixels::Color;
 use sdl2::rect::Rect;
 use sdl2::video::{Window, WindowContext};
-use sdl2::render::{Canvas, Texture, TextureCreator};
+use sdl2::render::{BlendMode, Canvas, Texture, TextureCreator};
 use sdl2::rwops::RWops;
-use crate::cells::Cell;
+use std::collections::HashMap;
+use sdl2::ttf::{Font, Sdl2TtfContext};
+use crate::brush::{BrushShape, PaintMode, Symmetry, Tool};
+use crate::cells::{Cell, MATERIALS, RadialSpawner, cell_name, same_material};
+use crate::game::{Clock, GameState, REGION_SIZE};
+use crate::material::{material_count, with_material};
 use crate::input;
+use crate::perf::Perf;
 
 pub const SCREEN_SIZE: i32 = 512;
+pub const PALETTE_HEIGHT: i32 = 20;
+/// Height of each row of key bindings
+const LABEL_HEIGHT: u32 = 16;
+/// Space left between key bindings on the same row
+const LABEL_GAP: i32 = 12;
 pub const MAP_SIZE: i32 = 256; // TODO move somewhere sensible
-pub const MOUSE_RATIO: f32 = MAP_SIZE as f32 / SCREEN_SIZE as f32;
+
+const MIN_ZOOM: f32 = 0.5;
+const MAX_ZOOM: f32 = 32.0;
+
+const SWATCH_SIZE: i32 = 16;
+/// Distance between the left edges of neighbouring swatches
+const SWATCH_STEP: i32 = 20;
+
+/// Maps between the world and the part of the screen it's drawn in
+pub struct Camera {
+    /// Area of the screen the world is drawn into
+    pub view: Rect,
+    /// World position at the top left of the view
+    pub x: f32,
+    pub y: f32,
+    /// Screen pixels per cell
+    pub zoom: f32,
+    /// Only allow whole numbers of pixels per cell
+    integer: bool,
+}
+
+/// Split a window into the world view, the palette and `bindings_height` of key bindings along the bottom
+pub fn layout(width: u32, height: u32, bindings_height: u32) -> (Rect, Rect, Rect) {
+    let hud_height = (PALETTE_HEIGHT as u32 + bindings_height).min(height.saturating_sub(1));
+    let view = Rect::new(0, 0, width.max(1), (height - hud_height).max(1));
+    let palette_height = (PALETTE_HEIGHT as u32).min(hud_height);
+    let palette = Rect::new(0, view.height() as i32, width.max(1), palette_height.max(1));
+    let hud = Rect::new(0, palette.bottom(), width.max(1), (hud_height - palette_height).max(1));
+    (view, palette, hud)
+}
+
+impl Camera {
+
+    pub fn new(view: Rect) -> Self {
+        let mut camera = Camera {
+            view,
+            x: 0.0,
+            y: 0.0,
+            zoom: 1.0,
+            integer: false,
+        };
+        camera.reset();
+        camera
+    }
+
+    fn snap(&self, zoom: f32) -> f32 {
+        let zoom = zoom.clamp(MIN_ZOOM, MAX_ZOOM);
+        if self.integer {
+            return zoom.floor().max(1.0);
+        }
+        zoom
+    }
+
+    /// Fit the whole world into the middle of the view
+    pub fn reset(&mut self) {
+        self.zoom = self.snap(self.view.width().min(self.view.height()) as f32 / MAP_SIZE as f32);
+        self.x = (MAP_SIZE as f32 - self.view.width() as f32 / self.zoom) / 2.0;
+        self.y = (MAP_SIZE as f32 - self.view.height() as f32 / self.zoom) / 2.0;
+    }
+
+    /// Move the view to a new area of the screen, scaling to keep showing the same part of the world
+    pub fn set_view(&mut self, view: Rect) {
+        let centre = self.view.center();
+        let (cx, cy) = self.to_world_f32(centre.x(), centre.y());
+        let scale = view.width().min(view.height()) as f32 / self.view.width().min(self.view.height()) as f32;
+        self.view = view;
+        self.zoom = self.snap(self.zoom * scale);
+        self.x = cx - view.width() as f32 / 2.0 / self.zoom;
+        self.y = cy - view.height() as f32 / 2.0 / self.zoom;
+    }
+
+    pub fn set_integer(&mut self, integer: bool) {
+        self.integer = integer;
+        self.set_view(self.view);
+    }
+
+    pub fn is_integer(&self) -> bool {
+        self.integer
+    }
+
+    fn to_world_f32(&self, sx: i32, sy: i32) -> (f32, f32) {
+        (self.x + (sx - self.view.x()) as f32 / self.zoom, self.y + (sy - self.view.y()) as f32 / self.zoom)
+    }
+
+    /// Cell under a point on the screen
+    pub fn to_world(&self, sx: i32, sy: i32) -> (i32, i32) {
+        let (wx, wy) = self.to_world_f32(sx, sy);
+        (wx.floor() as i32, wy.floor() as i32)
+    }
+
+    /// Screen position of the top left corner of a cell
+    pub fn to_screen(&self, wx: i32, wy: i32) -> (i32, i32) {
+        (self.view.x() + ((wx as f32 - self.x) * self.zoom).round() as i32,
+         self.view.y() + ((wy as f32 - self.y) * self.zoom).round() as i32)
+    }
+
+    /// Area of the screen covered by a world `size` cells across, usually bigger than the view
+    pub fn world_rect(&self, size: i32) -> Rect {
+        let (x, y) = self.to_screen(0, 0);
+        let side = (size as f32 * self.zoom).round() as u32;
+        Rect::new(x, y, side, side)
+    }
+
+    /// Zoom by `factor`, keeping whatever is under the screen point (sx, sy) in place
+    pub fn zoom_at(&mut self, sx: i32, sy: i32, factor: f32) {
+        let (wx, wy) = self.to_world_f32(sx, sy);
+        let zoom = match (self.integer, factor > 1.0) {
+            (true, true) => self.zoom.floor() + 1.0,
+            (true, false) => self.zoom.ceil() - 1.0,
+            (false, _) => self.zoom * factor
+        };
+        self.zoom = self.snap(zoom);
+        self.x = wx - (sx - self.view.x()) as f32 / self.zoom;
+        self.y = wy - (sy - self.view.y()) as f32 / self.zoom;
+    }
+
+    /// Move the view by a distance in screen pixels
+    pub fn pan(&mut self, dx: i32, dy: i32) {
+        self.x -= dx as f32 / self.zoom;
+        self.y -= dy as f32 / self.zoom;
+    }
+}
+
+/// Translucent cells over the world, for the brush and shapes that haven't been placed yet
+pub fn draw_preview(canvas: &mut Canvas<Window>, camera: &Camera, points: &[(i32, i32)], cell: Cell) {
+    let color = match cell {
+        Cell::Air => Color::RGB(255, 255, 255),
+        _ => get_cell_color(cell)
+    };
+    let side = camera.zoom.ceil().max(1.0) as u32;
+    let rects: Vec<Rect> = points.iter()
+        .map(|(x, y)| {
+            let (sx, sy) = camera.to_screen(*x, *y);
+            Rect::new(sx, sy, side, side)
+        })
+        .collect();
+    canvas.set_blend_mode(BlendMode::Blend);
+    canvas.set_draw_color(Color::RGBA(color.r, color.g, color.b, 128));
+    canvas.fill_rects(&rects).unwrap();
+    canvas.set_draw_color(Color::RGBA(0, 0, 0, 255));
+}
 
 pub fn get_cell_color(cell: Cell) -> Color {
     match cell {
-        Cell::Sand => {
-            if thread_rng().gen::<bool>() {
+        Cell::Sand{shade} => {
+            if shade < 128 {
                 return Color::RGB(164, 140, 3);
             }
             Color::RGB(180, 155, 3)
@@ -22,8 +172,8 @@ pub fn get_cell_color(cell: Cell) -> Color {
         Cell::Wood{..} => {
             Color::RGB(116, 43, 0)
         },
-        Cell::Fire{..} => {
-            if thread_rng().gen::<bool>() {
+        Cell::Fire{shade, ..} => {
+            if shade < 128 {
                 return Color::RGB(255, 102, 0);
             }
             Color::RGB(255, 0, 0)
@@ -34,8 +184,8 @@ pub fn get_cell_color(cell: Cell) -> Color {
         Cell::Vine{growth, ..} => {
             Color::RGB(0, 116 - (growth as u8), 11)
         }
-        Cell::Water{..} => {
-            if thread_rng().gen::<f32>() < 0.125 {
+        Cell::Water{shade, ..} => {
+            if shade < 32 {
                 return Color::RGB(0, 126, 255);
             }
             Color::RGB(0, 102, 255)
@@ -54,19 +204,221 @@ pub fn get_cell_color(cell: Cell) -> Color {
         },
         Cell::Ice => {
             Color::RGB(102, 204, 255)
+        },
+        Cell::Tap{..} => {
+            Color::RGB(80, 80, 140)
+        },
+        Cell::Void => {
+            Color::RGB(48, 0, 64)
+        },
+        Cell::Cloner{..} => {
+            Color::RGB(176, 48, 176)
+        },
+        Cell::Custom{id, state} => {
+            with_material(id, |m| m.color(state)).unwrap_or(Color::RGB(0, 0, 0))
         }
         _ => Color::RGB(0, 0, 0)
     }
 }
 
 
+/// Copy whatever changed in the world's picture since the last upload into `texture`
+pub fn upload_changes(texture: &mut Texture, state: &mut GameState) {
+    if let Some(r) = state.take_changed() {
+        let pitch = state.size as usize * 3;
+        let offset = r.y() as usize * pitch + r.x() as usize * 3;
+        texture.update(r, &state.pixels()[offset..], pitch).unwrap();
+    }
+}
+
+/// Material name and any state it carries, for the inspector
+pub fn describe_cell(cell: Cell) -> String {
+    match cell {
+        Cell::Tap{material, t} => {
+            format!("Tap {{ material: {}, t: {} }}", cell_name(MATERIALS[material]), t)
+        },
+        Cell::Cloner{material: Some(material)} => {
+            format!("Cloner {{ material: {} }}", cell_name(MATERIALS[material]))
+        },
+        Cell::Custom{state, ..} => {
+            format!("{} {{ state: {} }}", cell_name(cell), state)
+        },
+        _ => format!("{:?}", cell)
+    }
+}
+
+/// Frames a block stays highlighted after being woken
+const WAKE_FLASH_FRAMES: u8 = 20;
+
+/// Debug view of the dirty block optimisation
+pub struct BlockOverlay {
+    pub grid: bool,
+    pub activity: bool,
+    flashes: HashMap<(i32, i32), u8>,
+}
+
+impl BlockOverlay {
+
+    pub fn new() -> Self {
+        BlockOverlay {
+            grid: false,
+            activity: false,
+            flashes: HashMap::new(),
+        }
+    }
+
+    /// Start flashing any blocks woken during the last update, call after every tick
+    pub fn record(&mut self, state: &GameState) {
+        for pos in state.woken.iter() {
+            self.flashes.insert(*pos, WAKE_FLASH_FRAMES);
+        }
+    }
+
+    /// Draw over the world as the camera sees it
+    pub fn draw(&mut self, canvas: &mut Canvas<Window>, state: &GameState, camera: &Camera) {
+        // both corners from the camera, so blocks line up with the texture at any zoom
+        let block_rect = |(bx, by): (i32, i32)| {
+            let (x0, y0) = camera.to_screen(bx * REGION_SIZE, by * REGION_SIZE);
+            let (x1, y1) = camera.to_screen((bx + 1) * REGION_SIZE, (by + 1) * REGION_SIZE);
+            Rect::new(x0, y0, (x1 - x0).max(1) as u32, (y1 - y0).max(1) as u32)
+        };
+
+        canvas.set_blend_mode(BlendMode::Blend);
+        if self.activity {
+            for (pos, block) in state.blocks.iter() {
+                if block.dirty {
+                    canvas.set_draw_color(Color::RGBA(255, 64, 0, 48));
+                }
+                else {
+                    canvas.set_draw_color(Color::RGBA(0, 64, 255, 24));
+                }
+                canvas.fill_rect(block_rect(*pos)).unwrap();
+            }
+            for (pos, frames) in self.flashes.iter() {
+                let alpha = 160 * *frames as u32 / WAKE_FLASH_FRAMES as u32;
+                canvas.set_draw_color(Color::RGBA(255, 255, 0, alpha as u8));
+                canvas.fill_rect(block_rect(*pos)).unwrap();
+            }
+        }
+        if self.grid {
+            canvas.set_draw_color(Color::RGBA(128, 128, 128, 96));
+            for pos in state.blocks.keys() {
+                canvas.draw_rect(block_rect(*pos)).unwrap();
+            }
+        }
+        canvas.set_draw_color(Color::RGBA(0, 0, 0, 255));
+
+        // fade out once per frame, however many ticks ran
+        self.flashes.retain(|_, frames| {
+            *frames -= 1;
+            *frames > 0
+        });
+    }
+}
+
+/// What a click on the palette did
+pub enum PaletteClick {
+    Missed,
+    Page,
+    Select(Cell),
+}
+
+/// Row of clickable material swatches, split into pages when they don't fit across the window
+pub struct Palette {
+    pub area: Rect,
+    page: usize,
+}
+
+impl Palette {
+
+    pub fn new(area: Rect) -> Self {
+        Palette {
+            area,
+            page: 0,
+        }
+    }
+
+    /// Everything that can be painted, built-ins first then registered materials
+    pub fn cells() -> Vec<Cell> {
+        let mut cells = MATERIALS.to_vec();
+        cells.push(Cell::Void);
+        cells.push(Cell::Cloner{material: None});
+        cells.extend((0..material_count()).map(|id| Cell::Custom{id, state: 0}));
+        cells
+    }
+
+    /// Swatches that fit between the page buttons, leaving room for the page number after them
+    fn per_page(&self) -> usize {
+        ((self.area.width() as i32 / SWATCH_STEP) - 4).max(1) as usize
+    }
+
+    fn pages(&self, count: usize) -> usize {
+        count.div_ceil(self.per_page()).max(1)
+    }
+
+    /// Cells on the current page, paired with where they're drawn
+    pub fn visible(&self) -> Vec<(Cell, Rect)> {
+        let cells = Palette::cells();
+        let per_page = self.per_page();
+        let page = self.page.min(self.pages(cells.len()) - 1);
+        cells.into_iter()
+            .skip(page * per_page)
+            .take(per_page)
+            .enumerate()
+            .map(|(i, cell)| (cell, self.slot(i as i32 + 1)))
+            .collect()
+    }
+
+    /// The nth space along the palette, `0` is the previous page button
+    fn slot(&self, n: i32) -> Rect {
+        let inset = (SWATCH_STEP - SWATCH_SIZE) / 2;
+        let y = self.area.y() + (self.area.height() as i32 - SWATCH_SIZE) / 2;
+        Rect::new(self.area.x() + SWATCH_STEP * n + inset, y, SWATCH_SIZE as u32, SWATCH_SIZE as u32)
+    }
+
+    pub fn prev_button(&self) -> Rect {
+        self.slot(0)
+    }
+
+    pub fn next_button(&self) -> Rect {
+        self.slot(self.per_page() as i32 + 1)
+    }
+
+    /// Page number and count, for drawing
+    pub fn page(&self) -> (usize, usize) {
+        let pages = self.pages(Palette::cells().len());
+        (self.page.min(pages - 1), pages)
+    }
+
+    pub fn click(&mut self, x: i32, y: i32) -> PaletteClick {
+        if !self.area.contains_point((x, y)) {
+            return PaletteClick::Missed;
+        }
+        let (page, pages) = self.page();
+        if self.prev_button().contains_point((x, y)) {
+            self.page = (page + pages - 1) % pages;
+            return PaletteClick::Page;
+        }
+        if self.next_button().contains_point((x, y)) {
+            self.page = (page + 1) % pages;
+            return PaletteClick::Page;
+        }
+        match self.visible().into_iter().find(|(_, rect)| rect.contains_point((x, y))) {
+            Some((cell, _)) => PaletteClick::Select(cell),
+            // clicks between swatches shouldn't paint into the world either
+            None => PaletteClick::Page
+        }
+    }
+}
+
 pub struct Hud<'a> {
+    texture_creator: &'a TextureCreator<WindowContext>,
+    font: Font<'a, 'static>,
     keybinding_textures: Vec<Texture<'a>>,
 }
 
 impl<'a> Hud<'a> { 
-    pub fn new(texture_creator: &TextureCreator<WindowContext>) -> Hud {
-        let ttf_context = sdl2::ttf::init().map_err(|e| e.to_string()).unwrap();
+    pub fn new(texture_creator: &'a TextureCreator<WindowContext>, ttf_context: &'a Sdl2TtfContext) -> Hud<'a> {
         let bytes = include_bytes!("../Jazz_Ball_Regular.ttf");
         let font = ttf_context.load_font_from_rwops(RWops::from_bytes(bytes).unwrap(), 32).unwrap();
         
@@ -77,23 +429,176 @@ impl<'a> Hud<'a> {
         }
 
         Hud {
+            texture_creator,
+            font,
             keybinding_textures
         }
     }
 
-    pub fn draw(&self, canvas: &mut Canvas<Window>) {
-        // TODO track position and size of hud
-        let mut row = 0;
-        let mut col = 0;
-        let row_off = 16;
-        let col_off = 128;
+    /// Draw a line of text scaled to `height`, returning how wide it ended up
+    fn draw_text(&self, canvas: &mut Canvas<Window>, text: &str, x: i32, y: i32, height: u32) -> u32 {
+        let sur = self.font.render(text).blended(Color::RGBA(255, 255, 255, 255)).unwrap();
+        let tex = self.texture_creator.create_texture_from_surface(&sur).unwrap();
+        let width = sur.width() * height / sur.height().max(1);
+        canvas.copy(&tex, None, Rect::new(x, y, width, height)).unwrap();
+        width
+    }
+
+    fn text_width(&self, text: &str, height: u32) -> u32 {
+        match self.font.size_of(text) {
+            Ok((w, h)) => w * height / h.max(1),
+            Err(_) => 0
+        }
+    }
+
+    /// Show the cell at world position (x, y) and the state of its block
+    pub fn draw_inspector(&self, canvas: &mut Canvas<Window>, state: &GameState, x: i32, y: i32) {
+        if x < 0 || y < 0 || x >= state.size || y >= state.size {
+            return;
+        }
+        let (bx, by) = (x / REGION_SIZE, y / REGION_SIZE);
+        let dirty = state.blocks.get(&(bx, by)).is_some_and(|b| b.dirty);
+        let lines = [
+            describe_cell(*state.read_cell(x, y)),
+            format!("x {}  y {}", x, y),
+            format!("block {} {}  {}", bx, by, if dirty { "dirty" } else { "sleeping" }),
+        ];
+
+        canvas.set_blend_mode(BlendMode::Blend);
+        canvas.set_draw_color(Color::RGBA(0, 0, 0, 160));
+        canvas.fill_rect(Rect::new(0, 0, 256, 16 * lines.len() as u32 + 8)).unwrap();
+        canvas.set_draw_color(Color::RGBA(0, 0, 0, 255));
+        for (i, line) in lines.iter().enumerate() {
+            self.draw_text(canvas, line, 4, 4 + 16 * i as i32, 16);
+        }
+    }
+
+    /// Timings for the last second, in the top right corner
+    pub fn draw_perf(&self, canvas: &mut Canvas<Window>, perf: &Perf) {
+        let r = perf.report;
+        let lines = [
+            format!("FPS {}  TPS {}{}", r.fps, r.ticks, if perf.is_logging() { "  (csv)" } else { "" }),
+            format!("update {:.2} / {:.2} / {:.2} ms", r.update.min, r.update.avg, r.update.max),
+            format!("draw {:.2} / {:.2} / {:.2} ms", r.draw.min, r.draw.avg, r.draw.max),
+            format!("dirty {} / {} blocks", r.dirty_blocks, r.blocks),
+            format!("cells {}", r.live_cells),
+        ];
+
+        let width = 192;
+        let x = canvas.viewport().width() as i32 - width;
+        canvas.set_blend_mode(BlendMode::Blend);
+        canvas.set_draw_color(Color::RGBA(0, 0, 0, 160));
+        canvas.fill_rect(Rect::new(x, 0, width as u32, 16 * lines.len() as u32 + 8)).unwrap();
+        canvas.set_draw_color(Color::RGBA(0, 0, 0, 255));
+        for (i, line) in lines.iter().enumerate() {
+            self.draw_text(canvas, line, x + 4, 4 + 16 * i as i32, 16);
+        }
+    }
+
+    /// Swatches for the current page, outlining the materials on each mouse button
+    pub fn draw_palette(&self, canvas: &mut Canvas<Window>, palette: &Palette, spawner: &RadialSpawner) {
+        canvas.set_blend_mode(BlendMode::None);
+        for (cell, rect) in palette.visible() {
+            canvas.set_draw_color(get_cell_color(cell));
+            canvas.fill_rect(rect).unwrap();
+            let outline = match (same_material(cell, spawner.get_cell()), same_material(cell, spawner.get_secondary())) {
+                (true, _) => Some(Color::RGB(255, 255, 255)),
+                (false, true) => Some(Color::RGB(128, 128, 128)),
+                (false, false) => None
+            };
+            if let Some(color) = outline {
+                canvas.set_draw_color(color);
+                canvas.draw_rect(Rect::new(rect.x() - 2, rect.y() - 2, rect.width() + 4, rect.height() + 4)).unwrap();
+            }
+        }
+
+        let (page, pages) = palette.page();
+        if pages > 1 {
+            let (prev, next) = (palette.prev_button(), palette.next_button());
+            self.draw_text(canvas, "<", prev.x() + 4, prev.y(), prev.height());
+            self.draw_text(canvas, ">", next.x() + 4, next.y(), next.height());
+            self.draw_text(canvas, &format!("{}/{}", page + 1, pages), next.right() + 4, next.y(), next.height());
+        }
+        canvas.set_draw_color(Color::RGB(0, 0, 0));
+    }
+
+    /// Tool, brush and what the right button does, in the bottom left of the world view
+    pub fn draw_tool(&self, canvas: &mut Canvas<Window>, view: Rect, spawner: &RadialSpawner, clock: &Clock) {
+        let brush = &spawner.brush;
+        let tool = match (spawner.tool, spawner.filled) {
+            (Tool::Rect, false) | (Tool::Circle, false) => format!("{} outline", spawner.tool.name()),
+            (tool, _) => tool.name().to_owned()
+        };
+        let secondary = match (spawner.get_secondary(), spawner.get_filter()) {
+            (Cell::Air, Some(filter)) => format!("erase {}", cell_name(filter)),
+            (Cell::Air, None) => "erase".to_owned(),
+            (cell, _) => cell_name(cell)
+        };
+        let mode = match spawner.mode {
+            PaintMode::Overwrite => String::new(),
+            PaintMode::EmptyOnly => "  into Air".to_owned(),
+            PaintMode::Replace => format!("  over {}", cell_name(spawner.get_target()))
+        };
+        let density = match brush.shape() {
+            BrushShape::Spray => format!(" {}%", (brush.density() * 100.0).round()),
+            _ => String::new()
+        };
+        let mix = spawner.get_mix();
+        let total: u32 = mix.iter().map(|(_, weight)| weight).sum();
+        let primary = match mix.len() {
+            0 | 1 => String::new(),
+            _ => {
+                let parts: Vec<String> = mix.iter()
+                    .map(|(cell, weight)| format!("{} {}%", cell_name(*cell), weight * 100 / total))
+                    .collect();
+                format!("  L: {}", parts.join(" "))
+            }
+        };
+        let symmetry = match spawner.symmetry {
+            Symmetry::Off => String::new(),
+            symmetry => format!("  {}", symmetry.name())
+        };
+        let speed = match clock.speed() {
+            s if (s - 1.0).abs() < f32::EPSILON => String::new(),
+            s => format!("  ×{}", s)
+        };
+        let paused = if clock.is_paused() { "  paused" } else { "" };
+        let text = format!("{}  {} {}{}{}  R: {}{}{}{}{}", tool, brush.shape().name(), brush.radius() * 2 + 1, density, primary, secondary, mode, symmetry, paused, speed);
+
+        let y = view.bottom() - 24;
+        canvas.set_blend_mode(BlendMode::Blend);
+        canvas.set_draw_color(Color::RGBA(0, 0, 0, 160));
+        canvas.fill_rect(Rect::new(view.x(), y, self.text_width(&text, 16) + 8, 24)).unwrap();
+        canvas.set_draw_color(Color::RGBA(0, 0, 0, 255));
+        self.draw_text(canvas, &text, view.x() + 4, y + 4, 16);
+    }
+
+    /// Where each key binding goes in a HUD `width` pixels across, wrapping onto as many rows as it takes
+    fn arrange(&self, width: u32) -> Vec<Rect> {
+        let mut rects = Vec::new();
+        let (mut x, mut y) = (0, 0);
         for tex in self.keybinding_textures.iter() {
-            canvas.copy(&tex, None, Rect::new(0 + (col_off * col), 512 + (row_off * row), 64, 16)).unwrap();
-            row += 1;
-            if row >= 4 {
-                row = 0;
-                col += 1;
+            let query = tex.query();
+            let w = query.width * LABEL_HEIGHT / query.height.max(1);
+            if x > 0 && x + w as i32 > width as i32 {
+                x = 0;
+                y += LABEL_HEIGHT as i32;
             }
+            rects.push(Rect::new(x, y, w, LABEL_HEIGHT));
+            x += w as i32 + LABEL_GAP;
+        }
+        rects
+    }
+
+    /// How tall the key bindings are in a window `width` pixels across, for `layout`
+    pub fn bindings_height(&self, width: u32) -> u32 {
+        self.arrange(width).last().map_or(0, |r| r.bottom() as u32)
+    }
+
+    /// Draw the key bindings into `area` along the bottom of the window
+    pub fn draw(&self, canvas: &mut Canvas<Window>, area: Rect) {
+        for (tex, rect) in self.keybinding_textures.iter().zip(self.arrange(area.width())) {
+            canvas.copy(tex, None, Rect::new(area.x() + rect.x(), area.y() + rect.y(), rect.width(), rect.height())).unwrap();
         }
     }
 }
\ No newline at end of file
@@ -0,0 +1,281 @@
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant, SystemTime};
+use rhai::{Array, Engine, Map, Scope, AST};
+use rhai::module_resolvers::DummyModuleResolver;
+use sdl2::pixels::Color;
+
+use crate::cells::{Cell, cell_name, cell_from_name, update_if_on_boundary};
+use crate::game::{GameState, DIRECTIONS};
+use crate::material::{Material, Properties, register_material};
+
+/// Every `.rhai` file in here is loaded as a material, named after the file
+pub const SCRIPT_DIR: &str = "materials";
+
+/// How far from itself a script can read and write
+pub const RADIUS: i32 = 2;
+
+const RELOAD_INTERVAL: Duration = Duration::from_secs(1);
+
+/// What a script can see of the world, filled in before each call to `update`
+struct Neighbourhood {
+    cells: Vec<Cell>,
+    empty: Vec<bool>,
+    down: (i32, i32),
+    writes: Vec<(i32, i32, Cell, bool)>,
+}
+
+impl Neighbourhood {
+
+    fn index(dx: i64, dy: i64) -> Option<usize> {
+        let r = RADIUS as i64;
+        if dx < -r || dx > r || dy < -r || dy > r {
+            return None;
+        }
+        Some(((dy + r) * (2 * r + 1) + dx + r) as usize)
+    }
+
+    fn write(&mut self, dx: i64, dy: i64, cell: Option<Cell>, dirty: bool) {
+        if let (Some(_), Some(cell)) = (Neighbourhood::index(dx, dy), cell) {
+            self.writes.push((dx as i32, dy as i32, cell, dirty));
+        }
+    }
+}
+
+struct Script {
+    ast: AST,
+    properties: Properties,
+    has_color: bool,
+    modified: Option<SystemTime>,
+    checked: Instant,
+    failed: bool,
+}
+
+pub struct ScriptMaterial {
+    name: String,
+    path: PathBuf,
+    engine: Engine,
+    neighbourhood: Arc<Mutex<Neighbourhood>>,
+    script: Mutex<Script>,
+}
+
+impl ScriptMaterial {
+
+    pub fn load(path: &Path) -> Result<ScriptMaterial, String> {
+        let name = path.file_stem().ok_or("no file name")?.to_string_lossy().into_owned();
+        let neighbourhood = Arc::new(Mutex::new(Neighbourhood {
+            cells: Vec::new(),
+            empty: Vec::new(),
+            down: (0, 1),
+            writes: Vec::new(),
+        }));
+        let engine = sandboxed_engine(&name, &neighbourhood);
+        let modified = fs::metadata(path).and_then(|m| m.modified()).ok();
+        let (ast, properties, has_color) = compile(&engine, path)?;
+
+        Ok(ScriptMaterial {
+            name,
+            path: path.to_owned(),
+            engine,
+            neighbourhood,
+            script: Mutex::new(Script {
+                ast,
+                properties,
+                has_color,
+                modified,
+                checked: Instant::now(),
+                failed: false,
+            }),
+        })
+    }
+
+    /// Recompile if the file has changed since it was last loaded
+    fn reload(&self) {
+        let mut script = self.script.lock().unwrap();
+        if script.checked.elapsed() < RELOAD_INTERVAL {
+            return;
+        }
+        script.checked = Instant::now();
+
+        let modified = fs::metadata(&self.path).and_then(|m| m.modified()).ok();
+        if modified == script.modified {
+            return;
+        }
+        script.modified = modified;
+
+        // a broken edit keeps the last working version running
+        match compile(&self.engine, &self.path) {
+            Ok((ast, properties, has_color)) => {
+                script.ast = ast;
+                script.properties = properties;
+                script.has_color = has_color;
+                script.failed = false;
+                println!("Reloaded {}", self.path.display());
+            },
+            Err(e) => {
+                eprintln!("{}: {}", self.path.display(), e);
+            }
+        }
+    }
+}
+
+impl Material for ScriptMaterial {
+
+    fn name(&self) -> &str {
+        &self.name
+    }
+
+    fn color(&self, state: i32) -> Color {
+        let script = self.script.lock().unwrap();
+        if !script.has_color {
+            return Color::RGB(128, 128, 128);
+        }
+        match self.engine.call_fn::<Array>(&mut Scope::new(), &script.ast, "color", (state as i64,)) {
+            Ok(rgb) if rgb.len() == 3 => {
+                let c: Vec<u8> = rgb.iter().map(|v| v.as_int().unwrap_or(0).clamp(0, 255) as u8).collect();
+                Color::RGB(c[0], c[1], c[2])
+            },
+            _ => Color::RGB(255, 0, 255)
+        }
+    }
+
+    fn properties(&self) -> Properties {
+        self.script.lock().unwrap().properties
+    }
+
+    fn update(&self, state: i32, x: i32, y: i32, read_state: &GameState, write_state: &mut GameState) {
+        self.reload();
+
+        {
+            let mut n = self.neighbourhood.lock().unwrap();
+            n.cells.clear();
+            n.empty.clear();
+            n.writes.clear();
+            n.down = DIRECTIONS[read_state.gravity.down()];
+            for dy in -RADIUS..=RADIUS {
+                for dx in -RADIUS..=RADIUS {
+                    n.cells.push(*read_state.read_cell(x + dx, y + dy));
+                    n.empty.push(read_state.is_empty(x + dx, y + dy) && write_state.is_empty(x + dx, y + dy));
+                }
+            }
+        }
+
+        let mut script = self.script.lock().unwrap();
+        let result = self.engine.call_fn::<()>(&mut Scope::new(), &script.ast, "update", (state as i64,));
+        let writes = std::mem::take(&mut self.neighbourhood.lock().unwrap().writes);
+        match result {
+            Ok(_) => {
+                drop(script);
+                for (dx, dy, cell, dirty) in writes {
+                    write_state.write_cell(cell, x + dx, y + dy, dirty);
+                }
+                update_if_on_boundary(x, y, write_state);
+            },
+            Err(e) => {
+                // don't flood the console with the same error every tick
+                if !script.failed {
+                    eprintln!("{}: {}", self.path.display(), e);
+                    script.failed = true;
+                }
+                drop(script);
+                if let Some(cell) = cell_from_name(&self.name, state) {
+                    write_state.write_cell(cell, x, y, false);
+                }
+            }
+        }
+    }
+}
+
+/// Engine with no access to anything but the cells around the one being updated
+fn sandboxed_engine(name: &str, neighbourhood: &Arc<Mutex<Neighbourhood>>) -> Engine {
+    let mut engine = Engine::new();
+    engine.set_module_resolver(DummyModuleResolver::new());
+    engine.disable_symbol("eval");
+    engine.set_max_operations(100_000);
+    engine.set_max_call_levels(16);
+    engine.set_max_expr_depths(64, 32);
+    engine.set_max_string_size(256);
+    engine.set_max_array_size(256);
+    engine.set_max_map_size(64);
+
+    let n = neighbourhood.clone();
+    engine.register_fn("read", move |dx: i64, dy: i64| -> String {
+        let n = n.lock().unwrap();
+        match Neighbourhood::index(dx, dy) {
+            Some(i) => cell_name(n.cells[i]),
+            None => cell_name(Cell::Air)
+        }
+    });
+    let n = neighbourhood.clone();
+    engine.register_fn("is_empty", move |dx: i64, dy: i64| -> bool {
+        let n = n.lock().unwrap();
+        match Neighbourhood::index(dx, dy) {
+            Some(i) => n.empty[i],
+            None => false
+        }
+    });
+    let n = neighbourhood.clone();
+    engine.register_fn("write", move |dx: i64, dy: i64, name: &str| {
+        n.lock().unwrap().write(dx, dy, cell_from_name(name, 0), true);
+    });
+    let n = neighbourhood.clone();
+    engine.register_fn("write", move |dx: i64, dy: i64, name: &str, state: i64| {
+        n.lock().unwrap().write(dx, dy, cell_from_name(name, state as i32), true);
+    });
+    let n = neighbourhood.clone();
+    let own = name.to_owned();
+    engine.register_fn("rest", move |state: i64| {
+        n.lock().unwrap().write(0, 0, cell_from_name(&own, state as i32), false);
+    });
+    let n = neighbourhood.clone();
+    engine.register_fn("down", move || -> Array {
+        let (dx, dy) = n.lock().unwrap().down;
+        vec![(dx as i64).into(), (dy as i64).into()]
+    });
+    engine.register_fn("random", || -> f64 {
+        rand::random()
+    });
+    engine
+}
+
+fn compile(engine: &Engine, path: &Path) -> Result<(AST, Properties, bool), String> {
+    let source = fs::read_to_string(path).map_err(|e| e.to_string())?;
+    let ast = engine.compile(&source).map_err(|e| e.to_string())?;
+    if !ast.iter_functions().any(|f| f.name == "update") {
+        return Err("no update(state) function".to_owned());
+    }
+    let has_color = ast.iter_functions().any(|f| f.name == "color");
+
+    let mut properties = Properties::default();
+    if ast.iter_functions().any(|f| f.name == "properties") {
+        let map = engine.call_fn::<Map>(&mut Scope::new(), &ast, "properties", ()).map_err(|e| e.to_string())?;
+        let flag = |key: &str| map.get(key).and_then(|v| v.as_bool().ok()).unwrap_or(false);
+        properties.flammable = flag("flammable");
+        properties.soluble = flag("soluble");
+    }
+    Ok((ast, properties, has_color))
+}
+
+/// Register every script in `dir` as a material
+pub fn load_scripts(dir: impl AsRef<Path>) {
+    let entries = match fs::read_dir(dir) {
+        Ok(entries) => entries,
+        Err(_) => return
+    };
+    let mut paths: Vec<PathBuf> = entries.flatten().map(|e| e.path()).collect();
+    paths.sort();
+    for path in paths {
+        if path.extension().is_none_or(|e| e != "rhai") {
+            continue;
+        }
+        match ScriptMaterial::load(&path) {
+            Ok(material) => {
+                register_material(material);
+            },
+            Err(e) => {
+                eprintln!("{}: {}", path.display(), e);
+            }
+        }
+    }
+}